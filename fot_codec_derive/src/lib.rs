@@ -0,0 +1,317 @@
+//! `#[derive(Encodable)]` for `fot_codec` sections.
+//!
+//! Container attributes:
+//! - `#[encodable(section = "esh")]` - the struct starts with a `<esh>\0` header,
+//!   errors inside it get `esh` prepended to their section path.
+//!
+//! Field attributes:
+//! - `#[encodable(prefix = "u16")]` - `Vec` with a length prefix of the given type instead of `u32`.
+//! - `#[encodable(count = 0x48)]` - `Vec` with a fixed element count and no prefix.
+//! - `#[encodable(rest)]` - `Vec<u8>` holding everything up to the end of the stream.
+//! - `#[encodable(when = "*flag != -1")]` - `Option` present when the expression holds,
+//!   preceding fields are visible in it as references.
+//!
+//! Fields are otherwise parsed and written with their own `Encodable` impl, in declaration order.
+//! `Vec<u8>` fields, prefixed or not, are read and written as a single slice.
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Fields, GenericParam, Ident, LitInt, LitStr, Type,
+};
+
+#[proc_macro_derive(Encodable, attributes(encodable))]
+pub fn derive_encodable(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Plain,
+    Prefix(Type),
+    Count(LitInt),
+    Rest,
+    When(Expr),
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    kind: Kind,
+}
+
+// `Vec<u8>` is read and written as one slice instead of byte by byte
+fn is_bytes(ty: &Type) -> bool {
+    let Type::Path(p) = ty else { return false };
+    let Some(last) = p.path.segments.last() else {
+        return false;
+    };
+    let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+        return false;
+    };
+    last.ident == "Vec"
+        && matches!(
+            args.args.first(),
+            Some(syn::GenericArgument::Type(Type::Path(t))) if t.path.is_ident("u8")
+        )
+}
+
+impl Field {
+    // collections get their name in the section path, so element indices make sense
+    fn labeled(&self) -> bool {
+        match self.kind {
+            Kind::Prefix(_) | Kind::Count(_) | Kind::Rest => true,
+            Kind::When(_) => false,
+            Kind::Plain => match &self.ty {
+                Type::Array(_) => true,
+                Type::Path(p) => p.path.segments.last().is_some_and(|s| s.ident == "Vec"),
+                _ => false,
+            },
+        }
+    }
+}
+
+fn section_attr(input: &DeriveInput) -> syn::Result<Option<LitStr>> {
+    let mut section = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("encodable"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("section") {
+                section = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown encodable attribute"))
+            }
+        })?;
+    }
+    Ok(section)
+}
+
+fn field_kind(field: &syn::Field) -> syn::Result<Kind> {
+    let mut kind = Kind::Plain;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("encodable"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if !matches!(kind, Kind::Plain) {
+                return Err(meta.error("only one encodable attribute per field"));
+            }
+            kind = if meta.path.is_ident("prefix") {
+                Kind::Prefix(meta.value()?.parse::<LitStr>()?.parse()?)
+            } else if meta.path.is_ident("count") {
+                Kind::Count(meta.value()?.parse()?)
+            } else if meta.path.is_ident("rest") {
+                Kind::Rest
+            } else if meta.path.is_ident("when") {
+                Kind::When(meta.value()?.parse::<LitStr>()?.parse()?)
+            } else {
+                return Err(meta.error("unknown encodable attribute"));
+            };
+            Ok(())
+        })?;
+    }
+    Ok(kind)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(named) => named
+                .named
+                .iter()
+                .map(|f| {
+                    Ok(Field {
+                        ident: f.ident.clone().unwrap(),
+                        ty: f.ty.clone(),
+                        kind: field_kind(f)?,
+                    })
+                })
+                .collect::<syn::Result<Vec<_>>>()?,
+            Fields::Unit => Vec::new(),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "Encodable can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Encodable can only be derived for structs",
+            ))
+        }
+    };
+    let section = section_attr(&input)?;
+
+    let name = &input.ident;
+    let (_, ty_generics, where_clause) = input.generics.split_for_impl();
+    // reuse the struct's lifetime if it borrows from the stream, otherwise add one
+    let lifetime = input.generics.params.iter().find_map(|p| match p {
+        GenericParam::Lifetime(l) => Some(l.lifetime.clone()),
+        _ => None,
+    });
+    let mut impl_generics = input.generics.clone();
+    let lifetime = lifetime.unwrap_or_else(|| {
+        let l = syn::Lifetime::new("'__a", Span::call_site());
+        impl_generics.params.insert(0, syn::parse_quote!(#l));
+        l
+    });
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+
+    let krate = quote!(::fot_codec);
+    let codec = quote!(#krate::codec);
+
+    let names: Vec<_> = fields.iter().map(|f| &f.ident).collect();
+
+    let parse_fields = fields.iter().enumerate().map(|(i, f)| {
+        let ident = &f.ident;
+        let ty = &f.ty;
+        let counted = if is_bytes(ty) {
+            quote!(#codec::parse_bytes)
+        } else {
+            quote!(#codec::parse_counted)
+        };
+        let value = match &f.kind {
+            Kind::Plain if is_bytes(ty) => quote!(
+                <u32 as #codec::Encodable>::parse(__stream)
+                    .and_then(|len| #counted(__stream, len as usize))
+            ),
+            Kind::Plain => quote!(<#ty as #codec::Encodable>::parse(__stream)),
+            Kind::Prefix(p) => quote!(
+                <#p as #codec::Encodable>::parse(__stream)
+                    .and_then(|len| #counted(__stream, len as usize))
+            ),
+            Kind::Count(n) => quote!(#counted(__stream, #n)),
+            Kind::Rest => quote!(__stream.read_slice(__stream.remain()).map(|s| s.to_vec())),
+            Kind::When(cond) => {
+                let prev = &names[..i];
+                quote!({
+                    #(#[allow(unused_variables)] let #prev = &#prev;)*
+                    #cond
+                }
+                .then(|| #codec::Encodable::parse(__stream))
+                .transpose())
+            }
+        };
+        let value = if f.labeled() {
+            let label = ident.to_string();
+            quote!(#codec::error::ResultExt::within(#value, #label))
+        } else {
+            value
+        };
+        quote!(let #ident: #ty = #value?;)
+    });
+
+    let write_fields = fields.iter().enumerate().map(|(i, f)| {
+        let ident = &f.ident;
+        let label = ident.to_string();
+        let counted = if is_bytes(&f.ty) {
+            quote!(::std::io::Write::write_all(&mut stream, &self.#ident)?;)
+        } else {
+            quote!(#codec::write_counted(&self.#ident, &mut stream)?;)
+        };
+        match &f.kind {
+            Kind::Plain if is_bytes(&f.ty) => quote!(
+                let len = u32::try_from(self.#ident.len()).map_err(|_| {
+                    ::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidInput,
+                        format!("{} has too many elements: {}", #label, self.#ident.len()),
+                    )
+                })?;
+                #codec::Encodable::write(&len, &mut stream)?;
+                #counted
+            ),
+            Kind::Plain => quote!(#codec::Encodable::write(&self.#ident, &mut stream)?;),
+            Kind::Prefix(p) => quote!(
+                let len = <#p>::try_from(self.#ident.len()).map_err(|_| {
+                    ::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidInput,
+                        format!("{} has too many elements: {}", #label, self.#ident.len()),
+                    )
+                })?;
+                #codec::Encodable::write(&len, &mut stream)?;
+                #counted
+            ),
+            Kind::Count(n) => quote!(
+                if self.#ident.len() != #n {
+                    return Err(::std::io::Error::new(
+                        ::std::io::ErrorKind::InvalidInput,
+                        format!("{} must have {} elements, has {}", #label, #n, self.#ident.len()),
+                    ));
+                }
+                #counted
+            ),
+            Kind::Rest => quote!(::std::io::Write::write_all(&mut stream, &self.#ident)?;),
+            Kind::When(cond) => {
+                let prev = &names[..i];
+                let cond_str = quote!(#cond).to_string();
+                quote!(
+                    let expected = {
+                        #(#[allow(unused_variables)] let #prev = &self.#prev;)*
+                        #cond
+                    };
+                    match &self.#ident {
+                        Some(v) if expected => #codec::Encodable::write(v, &mut stream)?,
+                        None if !expected => {}
+                        _ => {
+                            return Err(::std::io::Error::new(
+                                ::std::io::ErrorKind::InvalidInput,
+                                format!("{} must be set exactly when {}", #label, #cond_str),
+                            ))
+                        }
+                    }
+                )
+            }
+        }
+    });
+
+    let (parse_body, write_header) = match &section {
+        Some(section) => {
+            let header = format!("<{}>\0", section.value());
+            let label = section.value().to_lowercase();
+            (
+                quote!(__stream.section(#label, |__stream| {
+                    const HEADER: &str = #header;
+                    #krate::assert_section!(__stream, HEADER);
+                    #(#parse_fields)*
+                    Ok(Self { #(#names),* })
+                })),
+                quote!(::std::io::Write::write_all(&mut stream, #header.as_bytes())?;),
+            )
+        }
+        None => (
+            quote!({
+                #(#parse_fields)*
+                Ok(Self { #(#names),* })
+            }),
+            quote!(),
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics #codec::Encodable<#lifetime> for #name #ty_generics #where_clause {
+            fn parse(
+                __stream: &mut #codec::stream::Stream<#lifetime>,
+            ) -> Result<Self, #codec::error::ParseError> {
+                #parse_body
+            }
+
+            fn write<__W: ::std::io::Write>(
+                &self,
+                mut stream: __W,
+            ) -> Result<(), ::std::io::Error> {
+                #write_header
+                #(#write_fields)*
+                Ok(())
+            }
+        }
+    })
+}
@@ -0,0 +1,573 @@
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, Subcommand};
+use fot_codec::codec::census::Census;
+use fot_codec::codec::primitive::FOTString;
+use fot_codec::codec::sections::campaign_save::{CampaignFile, CampaignSave};
+use fot_codec::codec::sections::esh::Esh;
+use fot_codec::codec::sections::saveh::Saveh;
+use fot_codec::codec::sections::sgd::SDG;
+use fot_codec::codec::sections::ssg::{SSGEntry, SSG};
+use fot_codec::codec::sections::world::World;
+use fot_codec::codec::stream::Stream;
+use fot_codec::codec::Encodable;
+use fot_codec::files::cam::Cam;
+use fot_codec::files::sav::Sav;
+use fot_codec::files::save::Save;
+use fot_codec::image::RgbaImage;
+use std::ffi::CString;
+use std::fmt::Debug;
+use std::fs;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+// written by extract next to the unpacked files, read back by pack
+const SAVEH_FILE: &str = "saveh.bin";
+const MANIFEST_FILE: &str = "campaign_save.txt";
+const FILES_DIR: &str = "files";
+
+#[derive(Parser)]
+#[command(name = "fot-save", about = "Inspect and repack Fallout Tactics saves")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the save header summary
+    Info { save: PathBuf },
+    /// List files embedded in the save
+    Ls { save: PathBuf },
+    /// Unpack the header and every embedded file into a directory
+    Extract { save: PathBuf, dir: PathBuf },
+    /// Rebuild a save from a directory made by extract
+    Pack { dir: PathBuf, save: PathBuf },
+    /// Write the header images as PNG files into a directory
+    Images { save: PathBuf, dir: PathBuf },
+    /// Replace one of the header images with a PNG file
+    SetImage {
+        save: PathBuf,
+        slot: usize,
+        png: PathBuf,
+        /// Write the result here instead of overwriting the save
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// Report broken entity links in the embedded maps
+    Check { save: PathBuf },
+    /// Count property types the decoder doesn't know yet, across many saves
+    Census { saves: Vec<PathBuf> },
+    /// Pretty-print a section by its path as shown in parse errors,
+    /// e.g. `saveh/tmp[2]` or `campaign_save/files[3]/world/ssg/values[812]/esh`
+    Dump { save: PathBuf, section: String },
+}
+
+fn main() -> anyhow::Result<()> {
+    let out = &mut std::io::stdout().lock();
+    match Cli::parse().command {
+        Command::Info { save } => info(&read_save(&fs::read(&save)?)?, out),
+        Command::Ls { save } => ls(&read_save(&fs::read(&save)?)?, out),
+        Command::Extract { save, dir } => extract(&read_save(&fs::read(&save)?)?, &dir),
+        Command::Pack { dir, save } => pack(&dir, &save),
+        Command::Images { save, dir } => images(&read_save(&fs::read(&save)?)?, &dir),
+        Command::SetImage {
+            save,
+            slot,
+            png,
+            out,
+        } => set_image(&save, slot, &png, out.as_deref()),
+        Command::Check { save } => check(&read_save(&fs::read(&save)?)?, out),
+        Command::Census { saves } => census(&saves, out),
+        Command::Dump { save, section } => dump(&read_save(&fs::read(&save)?)?, &section, out),
+    }
+}
+
+fn read_save(data: &[u8]) -> anyhow::Result<Save> {
+    Ok(Save::parse(&mut Stream::new(data))?)
+}
+
+fn info(save: &Save, out: &mut impl Write) -> anyhow::Result<()> {
+    let saveh = &save.saveh;
+    writeln!(out, "magic:    {}", saveh.magic.to_string_lossy())?;
+    writeln!(out, "version:  {}", saveh.version)?;
+    writeln!(out, "title:    {}", saveh.title())?;
+    writeln!(out, "campaign: {}", saveh.campaign())?;
+    writeln!(out, "mission:  {}", saveh.mission())?;
+    writeln!(out, "player:   {}", saveh.player_name())?;
+    match saveh.saved_at() {
+        Some(time) => writeln!(out, "saved at: {}", time)?,
+        None => writeln!(out, "saved at: {:?}", saveh.ints)?,
+    }
+    for (i, zar) in saveh.tmp.iter().enumerate() {
+        writeln!(
+            out,
+            "image {}:  {}x{}{}",
+            i,
+            zar.w,
+            zar.h,
+            if zar.palette.is_some() {
+                ""
+            } else {
+                " (empty)"
+            }
+        )?;
+    }
+    writeln!(out, "files:    {}", save.campaign_save.files.len())?;
+    Ok(())
+}
+
+fn ls(save: &Save, out: &mut impl Write) -> anyhow::Result<()> {
+    for (i, file) in save.campaign_save.files.iter().enumerate() {
+        writeln!(out, "{:>4} {:>10} {}", i, file.data.len(), &*file.path)?;
+    }
+    Ok(())
+}
+
+// embedded paths use backslashes, refuse anything that would escape the target directory
+fn local_path(dir: &Path, path: &str) -> anyhow::Result<PathBuf> {
+    let rel = PathBuf::from(path.replace('\\', "/"));
+    if !rel
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        bail!(
+            "refusing to extract {:?} outside of the target directory",
+            path
+        );
+    }
+    Ok(dir.join(FILES_DIR).join(rel))
+}
+
+fn string_kind(s: &FOTString) -> &'static str {
+    match s {
+        FOTString::Ascii(_) => "ascii",
+        FOTString::Win1251(_) => "win1251",
+        FOTString::Utf16(_) => "utf16",
+    }
+}
+
+fn extract(save: &Save, dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut saveh = Vec::new();
+    save.saveh.write(&mut saveh)?;
+    fs::write(dir.join(SAVEH_FILE), saveh)?;
+
+    let mut manifest = format!("{}\n", save.campaign_save.magic.to_string_lossy());
+    for file in &save.campaign_save.files {
+        let path = local_path(dir, &file.path)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &file.data).with_context(|| format!("writing {}", path.display()))?;
+        manifest += &format!("{}\t{}\n", string_kind(&file.path), &*file.path);
+    }
+    fs::write(dir.join(MANIFEST_FILE), manifest)?;
+    Ok(())
+}
+
+fn pack(dir: &Path, out: &Path) -> anyhow::Result<()> {
+    let saveh_data = fs::read(dir.join(SAVEH_FILE))?;
+    let saveh = Saveh::parse(&mut Stream::new(&saveh_data))?;
+
+    let manifest = fs::read_to_string(dir.join(MANIFEST_FILE))?;
+    let mut lines = manifest.lines();
+    let magic = CString::new(lines.next().ok_or_else(|| anyhow!("empty manifest"))?)?;
+    let files = lines
+        .map(|line| {
+            let (kind, path) = line
+                .split_once('\t')
+                .ok_or_else(|| anyhow!("malformed manifest line {:?}", line))?;
+            let path = match kind {
+                "ascii" => FOTString::Ascii(path.to_owned()),
+                "win1251" => FOTString::Win1251(path.to_owned()),
+                "utf16" => FOTString::Utf16(path.to_owned()),
+                _ => bail!("unknown string kind {:?}", kind),
+            };
+            let local = local_path(dir, &path)?;
+            let data = fs::read(&local).with_context(|| format!("reading {}", local.display()))?;
+            Ok(CampaignFile { path, data })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    let save = Save {
+        saveh,
+        campaign_save: CampaignSave { magic, files },
+    };
+    let mut data = Vec::new();
+    save.write(&mut data)?;
+    fs::write(out, data)?;
+    Ok(())
+}
+
+fn images(save: &Save, dir: &Path) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    for (i, zar) in save.saveh.tmp.iter().enumerate() {
+        let image = zar
+            .to_rgba()
+            .with_context(|| format!("decoding image {}", i))?;
+        let path = dir.join(format!("image{}.png", i));
+        let file = fs::File::create(&path)?;
+        image
+            .write_png(std::io::BufWriter::new(file))
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    Ok(())
+}
+
+fn set_image(path: &Path, slot: usize, png: &Path, out: Option<&Path>) -> anyhow::Result<()> {
+    let mut save = read_save(&fs::read(path)?)?;
+    let zar = save
+        .saveh
+        .tmp
+        .get_mut(slot)
+        .ok_or_else(|| anyhow!("no image slot {}", slot))?;
+    let image = RgbaImage::read_png(std::io::BufReader::new(fs::File::open(png)?))
+        .with_context(|| format!("reading {}", png.display()))?;
+    zar.set_rgba(&image)?;
+
+    let mut data = Vec::new();
+    save.write(&mut data)?;
+    fs::write(out.unwrap_or(path), data)?;
+    Ok(())
+}
+
+fn check(save: &Save, out: &mut impl Write) -> anyhow::Result<()> {
+    let mut broken = 0;
+    for file in &save.campaign_save.files {
+        if file.extension() != "sav" {
+            continue;
+        }
+        let sav = Sav::parse(&mut Stream::new(&file.data))
+            .with_context(|| format!("parsing {}", &*file.path))?;
+        if let Err(errors) = sav.world.ssg.check_links() {
+            for e in &errors {
+                writeln!(out, "{}: {}", &*file.path, e)?;
+            }
+            broken += errors.len();
+        }
+    }
+    if broken != 0 {
+        bail!("{} broken links", broken);
+    }
+    Ok(())
+}
+
+fn census(saves: &[PathBuf], out: &mut impl Write) -> anyhow::Result<()> {
+    let mut census = Census::default();
+    for path in saves {
+        let save = read_save(&fs::read(path)?).with_context(|| format!("{}", path.display()))?;
+        census
+            .add_save(&save)
+            .with_context(|| format!("{}", path.display()))?;
+    }
+    write!(out, "{}", census)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Segment<'p> {
+    Name(&'p str),
+    Index(usize),
+}
+
+// `files[3]/world` is files, 3, world, like the section paths of parse errors
+fn parse_path(path: &str) -> anyhow::Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    for part in path.split('/').filter(|p| !p.is_empty()) {
+        let mut parts = part.split('[');
+        let name = parts.next().unwrap_or_default();
+        if !name.is_empty() {
+            segments.push(Segment::Name(name));
+        }
+        for index in parts {
+            let index = index
+                .strip_suffix(']')
+                .and_then(|i| i.parse().ok())
+                .ok_or_else(|| anyhow!("malformed index in {:?}", part))?;
+            segments.push(Segment::Index(index));
+        }
+    }
+    Ok(segments)
+}
+
+// sections that can be walked into, everything else is only printed
+enum Node<'a> {
+    Save(&'a Save),
+    Sav(&'a Sav),
+    Cam(&'a Cam),
+    Saveh(&'a Saveh),
+    CampaignSave(&'a CampaignSave),
+    Files(&'a [CampaignFile]),
+    World(&'a World),
+    Sdg(&'a SDG),
+    Ssg(&'a SSG),
+    Entries(&'a [SSGEntry]),
+    Entry(&'a SSGEntry),
+    Esh(&'a Esh),
+    List(Vec<&'a dyn Debug>),
+    Leaf(&'a dyn Debug),
+}
+
+impl Node<'_> {
+    fn dump(&self) -> String {
+        match self {
+            Node::Save(v) => format!("{:#?}", v),
+            Node::Sav(v) => format!("{:#?}", v),
+            Node::Cam(v) => format!("{:#?}", v),
+            Node::Saveh(v) => format!("{:#?}", v),
+            Node::CampaignSave(v) => format!("{:#?}", v),
+            Node::Files(v) => format!("{:#?}", v),
+            Node::World(v) => format!("{:#?}", v),
+            Node::Sdg(v) => format!("{:#?}", v),
+            Node::Ssg(v) => format!("{:#?}", v),
+            Node::Entries(v) => format!("{:#?}", v),
+            Node::Entry(v) => format!("{:#?}", v),
+            Node::Esh(v) => format!("{:#?}", v),
+            Node::List(v) => format!("{:#?}", v),
+            Node::Leaf(v) => format!("{:#?}", v),
+        }
+    }
+}
+
+fn list<T: Debug>(items: &[T]) -> Node<'_> {
+    Node::List(items.iter().map(|i| i as &dyn Debug).collect())
+}
+
+fn get<T>(items: &[T], i: usize) -> anyhow::Result<&T> {
+    items
+        .get(i)
+        .ok_or_else(|| anyhow!("index {} out of range, there are {}", i, items.len()))
+}
+
+// embedded files are parsed on the way, so the rest of the path is walked from here
+fn select_file(file: &CampaignFile, path: &[Segment]) -> anyhow::Result<String> {
+    let mut stream = Stream::new(&file.data);
+    match &*file.extension() {
+        "sav" => select(Node::Sav(&Sav::parse(&mut stream)?), path),
+        "cam" => select(Node::Cam(&Cam::parse(&mut stream)?), path),
+        _ if path.is_empty() => Ok(format!("{:#?}", file)),
+        ext => bail!("don't know how to walk into .{} files", ext),
+    }
+}
+
+fn select(node: Node, path: &[Segment]) -> anyhow::Result<String> {
+    use Segment::{Index, Name};
+
+    let Some((segment, rest)) = path.split_first() else {
+        return Ok(node.dump());
+    };
+    let child = match (node, *segment) {
+        (Node::Save(Save { saveh, .. }) | Node::Sav(Sav { saveh, .. }), Name("saveh")) => {
+            Node::Saveh(saveh)
+        }
+        (Node::Save(s), Name("campaign_save")) => Node::CampaignSave(&s.campaign_save),
+        (Node::Sav(s), Name("world")) => Node::World(&s.world),
+        (Node::Cam(c), Name("campaign")) => Node::Leaf(&c.campaign),
+        (Node::Saveh(s), Name("strings")) => list(&s.strings),
+        (Node::Saveh(s), Name("tmp")) => list(&s.tmp),
+        (Node::CampaignSave(c), Name("files")) => Node::Files(&c.files),
+        (Node::Files(files), Index(i)) => return select_file(get(files, i)?, rest),
+        (Node::World(w), Name("sdg")) => Node::Sdg(&w.sdg),
+        (Node::World(w), Name("ssg")) => Node::Ssg(&w.ssg),
+        (Node::Sdg(s), Name("names")) => list(&s.names),
+        (Node::Sdg(s), Name("replicas")) => list(&s.replicas),
+        (Node::Ssg(s), Name("entity_file")) => Node::Leaf(&s.entity_file),
+        (Node::Ssg(s), Name("values")) => Node::Entries(&s.values),
+        (Node::Entries(entries), Index(i)) => Node::Entry(get(entries, i)?),
+        (Node::Entry(e), Name("esh")) => match &e.data {
+            Some(esh) => Node::Esh(esh),
+            None => bail!("entry {} is a placeholder without data", e.id),
+        },
+        (Node::Esh(esh), Name("values")) => list(&esh.values),
+        (Node::List(items), Index(i)) => Node::Leaf(*get(&items, i)?),
+        (_, segment) => bail!("no section {:?} here", segment),
+    };
+    select(child, rest)
+}
+
+fn dump(save: &Save, section: &str, out: &mut impl Write) -> anyhow::Result<()> {
+    let path = parse_path(section)?;
+    let dumped = select(Node::Save(save), &path).with_context(|| format!("dumping {}", section))?;
+    writeln!(out, "{}", dumped)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use fot_codec::codec::sections::entity_file::EntityFile;
+    use fot_codec::codec::sections::esh::{EshEntry, EshValue};
+    use fot_codec::codec::sections::world::SecondLength;
+    use fot_codec::codec::sections::zar::Zar;
+
+    fn sample_save() -> Save {
+        let magic = || CString::new("V1").unwrap();
+        let zar = Zar {
+            magic: magic(),
+            h: 1,
+            w: 1,
+            present: 0,
+            palette: None,
+            // one transparent pixel
+            runs: vec![0b100],
+        };
+        let entry = |name: &str, value| EshEntry {
+            name: FOTString::Ascii(name.to_owned()),
+            value,
+        };
+        let saveh = || Saveh {
+            magic: magic(),
+            version: 1,
+            strings: std::array::from_fn(|i| FOTString::Ascii(format!("string {}", i))),
+            tmp: std::array::from_fn(|_| zar.clone()),
+            ints: [0; 6],
+        };
+        let world = World {
+            magic: magic(),
+            path: FOTString::Ascii("maps\\test.bos".to_owned()),
+            sdg: SDG {
+                magic: magic(),
+                unknown: vec![0; 0x48],
+                names: vec![FOTString::Ascii("Joe".to_owned())],
+                replicas: vec![vec![FOTString::Ascii("Hi".to_owned())]],
+            },
+            ssg: SSG {
+                unknown: [0; 0x16],
+                entity_file: EntityFile {
+                    magic: magic(),
+                    data: vec![FOTString::Ascii("entities\\human.ent".to_owned())],
+                },
+                unknown1: 0,
+                values: vec![SSGEntry {
+                    id: 1,
+                    flag: 0,
+                    data: Some(Esh {
+                        magic: magic(),
+                        values: vec![
+                            entry("Hit Points", EshValue::I32(30)),
+                            entry(
+                                "Target",
+                                EshValue::Link {
+                                    flags: 0,
+                                    entity: 7,
+                                },
+                            ),
+                        ],
+                    }),
+                }],
+            },
+            tail: vec![],
+            second_length: SecondLength::Uncompressed,
+            compression: Compression::fast(),
+            original: None,
+        };
+        let mut sav = Vec::new();
+        Sav {
+            saveh: saveh(),
+            world,
+        }
+        .write(&mut sav)
+        .unwrap();
+        Save {
+            saveh: saveh(),
+            campaign_save: CampaignSave {
+                magic: magic(),
+                files: vec![
+                    CampaignFile {
+                        path: FOTString::Ascii("save\\map.sav".to_owned()),
+                        data: sav,
+                    },
+                    CampaignFile {
+                        path: FOTString::Win1251("save\\заметки.txt".to_owned()),
+                        data: vec![1, 2, 3],
+                    },
+                ],
+            },
+        }
+    }
+
+    fn output(f: impl FnOnce(&mut Vec<u8>) -> anyhow::Result<()>) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        f(&mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn dump_walks_section_paths() {
+        let save = sample_save();
+        let dumped = |path| output(|out| dump(&save, path, out));
+
+        assert!(dumped("saveh").unwrap().starts_with("Saveh"));
+        assert!(dumped("saveh/strings[2]").unwrap().contains("string 2"));
+        assert!(dumped("saveh/tmp[7]").unwrap().starts_with("Zar"));
+        assert!(dumped("campaign_save/files[0]/world/sdg/replicas[0]")
+            .unwrap()
+            .contains("Hi"));
+        let property = dumped("campaign_save/files[0]/world/ssg/values[0]/esh/values[1]").unwrap();
+        assert!(property.contains("Target") && !property.contains("Hit Points"));
+        assert!(dumped("campaign_save/files[1]")
+            .unwrap()
+            .contains("заметки"));
+
+        assert!(dumped("saveh/tmp[8]").is_err());
+        assert!(dumped("campaign_save/files[1]/world").is_err());
+        assert!(dumped("campaign_save/files[0]/world/nothing").is_err());
+        assert!(dumped("saveh/tmp[x]").is_err());
+    }
+
+    #[test]
+    fn check_reports_broken_links() {
+        let save = sample_save();
+        let mut out = Vec::new();
+        let err = check(&save, &mut out).unwrap_err();
+        assert_eq!(err.to_string(), "1 broken links");
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("save\\map.sav: ") && out.contains("Target"));
+        assert!(output(|out| ls(&save, out))
+            .unwrap()
+            .contains("save\\map.sav"));
+        assert!(output(|out| info(&save, out))
+            .unwrap()
+            .contains("title:    string 0"));
+    }
+
+    #[test]
+    fn census_reads_saves_from_disk() {
+        let dir = std::env::temp_dir().join(format!("fot-save-census-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.sav");
+        let mut data = Vec::new();
+        sample_save().write(&mut data).unwrap();
+        fs::write(&path, data).unwrap();
+
+        let report = output(|out| census(&[path.clone(), path], out));
+        fs::remove_dir_all(&dir).unwrap();
+        // every tag in the sample is decoded
+        assert_eq!(report.unwrap(), "");
+    }
+
+    #[test]
+    fn extract_and_pack_round_trip() {
+        let dir = std::env::temp_dir().join(format!("fot-save-pack-{}", std::process::id()));
+        let save = sample_save();
+        let mut original = Vec::new();
+        save.write(&mut original).unwrap();
+
+        extract(&save, &dir).unwrap();
+        let manifest = fs::read_to_string(dir.join(MANIFEST_FILE)).unwrap();
+        assert_eq!(
+            manifest,
+            "V1\nascii\tsave\\map.sav\nwin1251\tsave\\заметки.txt\n"
+        );
+        let packed = dir.join("packed.sav");
+        pack(&dir, &packed).unwrap();
+        let repacked = fs::read(&packed).unwrap();
+
+        fs::write(dir.join(MANIFEST_FILE), "V1\nascii\t..\\escape.txt\n").unwrap();
+        let escape = pack(&dir, &packed);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(repacked, original);
+        assert!(escape.is_err());
+    }
+}
@@ -1,14 +1,22 @@
-use crate::codec::error::ParseError;
+use crate::codec::error::{ParseError, ResultExt};
 use crate::codec::stream::Stream;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
+use std::ffi::{CStr, CString};
 use std::io::{Error, Write};
 
+pub use fot_codec_derive::Encodable;
+
+pub mod census;
 pub mod error;
 pub mod format;
+pub mod math;
 pub mod primitive;
 pub mod sections;
+#[cfg(feature = "serde")]
+pub mod ser;
 pub mod shared;
 pub mod stream;
+pub mod verify;
 
 pub trait Encodable<'a>
 where
@@ -18,6 +26,70 @@ where
     fn write<T: Write>(&self, stream: T) -> Result<(), Error>;
 }
 
+// upper bound of the memory reserved up front for counted elements, in bytes
+const PREALLOC_BYTES: usize = 64 * 1024;
+
+/// Parses `len` elements without a length prefix.
+pub fn parse_counted<'a, T: Encodable<'a>>(
+    data: &mut Stream<'a>,
+    len: usize,
+) -> Result<Vec<T>, ParseError> {
+    // len comes from the input and an element can be much larger in memory than
+    // on disk, so preallocation is bounded in bytes and the vector grows past it
+    let max = PREALLOC_BYTES / std::mem::size_of::<T>().max(1);
+    let mut res = Vec::with_capacity(len.min(data.remain()).min(max));
+    for i in 0..len {
+        res.push(T::parse(data).at(i)?);
+    }
+    Ok(res)
+}
+
+/// Reads `len` bytes at once, the fast path of [`parse_counted`] for `Vec<u8>`.
+pub fn parse_bytes(data: &mut Stream, len: usize) -> Result<Vec<u8>, ParseError> {
+    Ok(data.read_slice(len)?.to_vec())
+}
+
+/// Writes elements without a length prefix.
+pub fn write_counted<'a, T: Encodable<'a>, W: Write>(
+    values: &[T],
+    mut stream: W,
+) -> Result<(), Error> {
+    for i in values {
+        i.write(&mut stream)?;
+    }
+    Ok(())
+}
+
+impl<'a> Encodable<'a> for i8 {
+    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
+        data.read_i8()
+    }
+
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_i8(*self)
+    }
+}
+
+impl<'a> Encodable<'a> for u16 {
+    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
+        data.read_u16()
+    }
+
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_u16::<LittleEndian>(*self)
+    }
+}
+
+impl<'a> Encodable<'a> for i16 {
+    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
+        data.read_i16()
+    }
+
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_i16::<LittleEndian>(*self)
+    }
+}
+
 impl<'a> Encodable<'a> for i32 {
     fn parse(data: &mut Stream) -> Result<Self, ParseError> {
         data.read_i32()
@@ -40,7 +112,7 @@ impl<'a> Encodable<'a> for u32 {
 
 impl<'a> Encodable<'a> for u8 {
     fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        Ok(data.read_u8()?)
+        data.read_u8()
     }
 
     fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
@@ -50,7 +122,7 @@ impl<'a> Encodable<'a> for u8 {
 
 impl<'a> Encodable<'a> for f32 {
     fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        Ok(data.read_f32::<LittleEndian>()?)
+        data.read_f32()
     }
 
     fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
@@ -58,31 +130,44 @@ impl<'a> Encodable<'a> for f32 {
     }
 }
 
+impl<'a> Encodable<'a> for &'a CStr {
+    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
+        data.read_cstr()
+    }
+
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_all(self.to_bytes_with_nul())
+    }
+}
+
+impl<'a> Encodable<'a> for CString {
+    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
+        Ok(data.read_cstr()?.to_owned())
+    }
+
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_all(self.to_bytes_with_nul())
+    }
+}
+
 impl<'a, T> Encodable<'a> for Vec<T>
 where
     T: Encodable<'a>,
 {
     fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
         let len = data.read_u32()?;
-        let mut res = Vec::with_capacity(len as _);
-        for _ in 0..len {
-            res.push(T::parse(data)?);
-        }
-        Ok(res)
+        parse_counted(data, len as usize)
     }
 
     fn write<T1: Write>(&self, mut stream: T1) -> Result<(), Error> {
         stream.write_u32::<LittleEndian>(self.len() as u32)?;
-        for i in self {
-            i.write(&mut stream)?;
-        }
-        Ok(())
+        write_counted(self, stream)
     }
 }
 
 impl<'a, T: Encodable<'a>, const N: usize> Encodable<'a> for [T; N] {
     fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        std::array::try_from_fn(|_| T::parse(data))
+        std::array::try_from_fn(|i| T::parse(data).at(i))
     }
 
     fn write<T1: Write>(&self, mut stream: T1) -> Result<(), Error> {
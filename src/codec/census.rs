@@ -0,0 +1,108 @@
+//! Survey of `EshValue` tags that still decode as `Unknown`, to guide reverse engineering.
+
+use crate::codec::error::{ParseError, ResultExt};
+use crate::codec::primitive::FOTString;
+use crate::codec::sections::esh::{Esh, EshValue};
+use crate::codec::sections::ssg::SSG;
+use crate::codec::stream::Stream;
+use crate::codec::Encodable;
+use crate::files::sav::Sav;
+use crate::files::save::Save;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Default)]
+pub struct TagStats {
+    pub count: usize,
+    /// Property names the tag was seen with, and how often.
+    pub names: BTreeMap<String, usize>,
+    /// Payload lengths, and how often.
+    pub lengths: BTreeMap<usize, usize>,
+    /// Payloads that are exactly one `FOTString`.
+    pub strings: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Census {
+    pub tags: BTreeMap<u32, TagStats>,
+    /// Decoded tags kept as `Unknown` because the payload didn't match their layout.
+    pub mismatched: BTreeMap<u32, TagStats>,
+}
+
+impl Census {
+    pub fn add_esh(&mut self, esh: &Esh) {
+        for entry in &esh.values {
+            let EshValue::Unknown(tag, data) = &entry.value else {
+                continue;
+            };
+            let tags = if EshValue::is_decoded(*tag) {
+                &mut self.mismatched
+            } else {
+                &mut self.tags
+            };
+            let stats = tags.entry(*tag).or_default();
+            stats.count += 1;
+            *stats.names.entry(entry.name.to_string()).or_default() += 1;
+            *stats.lengths.entry(data.len()).or_default() += 1;
+            let mut stream = Stream::new(data);
+            if FOTString::parse(&mut stream).is_ok() && stream.remain() == 0 {
+                stats.strings += 1;
+            }
+        }
+    }
+
+    pub fn add_ssg(&mut self, ssg: &SSG) {
+        for esh in ssg.values.iter().filter_map(|e| e.data.as_ref()) {
+            self.add_esh(esh);
+        }
+    }
+
+    /// Adds every map embedded in the save.
+    pub fn add_save(&mut self, save: &Save) -> Result<(), ParseError> {
+        for (i, file) in save.campaign_save.files.iter().enumerate() {
+            if file.extension() == "sav" {
+                let sav = Sav::parse(&mut Stream::new(&file.data))
+                    .at(i)
+                    .within("files")?;
+                self.add_ssg(&sav.world.ssg);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Display for Census {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (tag, stats) in &self.tags {
+            writeln!(f, "tag {}: {} values", tag, stats.count)?;
+            write!(f, "{}", stats)?;
+        }
+        for (tag, stats) in &self.mismatched {
+            writeln!(
+                f,
+                "tag {}: {} values that didn't match its layout",
+                tag, stats.count
+            )?;
+            write!(f, "{}", stats)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TagStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (name, count) in &self.names {
+            writeln!(f, "    {:?} x{}", name, count)?;
+        }
+        let lengths: Vec<_> = self
+            .lengths
+            .iter()
+            .map(|(len, count)| format!("{} bytes x{}", len, count))
+            .collect();
+        writeln!(f, "    lengths: {}", lengths.join(", "))?;
+        if self.strings != 0 {
+            writeln!(f, "    {} look like strings", self.strings)?;
+        }
+        Ok(())
+    }
+}
@@ -1,8 +1,200 @@
+use std::fmt::{Display, Formatter};
 use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    Name(&'static str),
+    Index(usize),
+}
+
+/// Location of an error inside nested sections, e.g. `world/ssg/values[812]/esh/values[4]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SectionPath(pub Vec<PathSegment>);
+
+impl Display for SectionPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "<root>");
+        }
+        for (i, seg) in self.0.iter().enumerate() {
+            match seg {
+                PathSegment::Name(name) if i == 0 => write!(f, "{}", name)?,
+                PathSegment::Name(name) => write!(f, "/{}", name)?,
+                PathSegment::Index(idx) => write!(f, "[{}]", idx)?,
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
-    #[error("Invalid section")]
-    InvalidSection(&'static str, String),
+    #[error("invalid section at offset {offset:#x}: expected {expected:?}, found {found:?}")]
+    InvalidSection {
+        offset: usize,
+        expected: &'static str,
+        found: String,
+    },
+    #[error("unexpected end of data at offset {offset:#x}, wanted {wanted} bytes, {remain} left")]
+    UnexpectedEof {
+        offset: usize,
+        wanted: usize,
+        remain: usize,
+    },
+    #[error("unterminated string at offset {offset:#x}")]
+    UnterminatedString { offset: usize },
+    #[error("invalid value at offset {offset:#x}: expected {expected}, found {found}")]
+    InvalidValue {
+        offset: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("{path}: {source}")]
+    Within {
+        path: SectionPath,
+        source: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    /// Innermost error, without the section path.
+    pub fn root(&self) -> &ParseError {
+        match self {
+            ParseError::Within { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    pub fn path(&self) -> SectionPath {
+        match self {
+            ParseError::Within { path, .. } => path.clone(),
+            _ => SectionPath::default(),
+        }
+    }
+
+    /// Offset of the failed read, relative to the stream it happened in.
+    /// Errors inside `world` are relative to the inflated world payload.
+    pub fn offset(&self) -> Option<usize> {
+        match self.root() {
+            ParseError::InvalidSection { offset, .. }
+            | ParseError::UnexpectedEof { offset, .. }
+            | ParseError::UnterminatedString { offset }
+            | ParseError::InvalidValue { offset, .. } => Some(*offset),
+            _ => None,
+        }
+    }
+
+    pub fn within(self, segment: PathSegment) -> Self {
+        match self {
+            ParseError::Within { mut path, source } => {
+                path.0.insert(0, segment);
+                ParseError::Within { path, source }
+            }
+            e => ParseError::Within {
+                path: SectionPath(vec![segment]),
+                source: Box::new(e),
+            },
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VerifyError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Inside `world` the offset is relative to the inflated world payload, like parse errors.
+    #[error("re-written data differs at offset {offset:#x} in {path}: expected {expected:?}, found {found:?}")]
+    Mismatch {
+        offset: usize,
+        path: SectionPath,
+        expected: Option<u8>,
+        found: Option<u8>,
+    },
+}
+
+impl VerifyError {
+    pub fn within(self, segment: PathSegment) -> Self {
+        match self {
+            VerifyError::Parse(e) => VerifyError::Parse(e.within(segment)),
+            VerifyError::Mismatch {
+                offset,
+                mut path,
+                expected,
+                found,
+            } => {
+                path.0.insert(0, segment);
+                VerifyError::Mismatch {
+                    offset,
+                    path,
+                    expected,
+                    found,
+                }
+            }
+            e => e,
+        }
+    }
+}
+
+/// Rejected edits of a decoded section, the section is left unchanged.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    #[error("{0:?} can't be encoded as Windows-1251")]
+    Unencodable(String),
+    #[error("{what} out of range: {value}")]
+    OutOfRange { what: &'static str, value: i64 },
+    #[error("at most {max} {what} allowed")]
+    TooMany { what: &'static str, max: usize },
+    #[error("no entity with id {0}")]
+    NoEntity(i32),
+    #[error("entity {id} is not a {expected}")]
+    WrongKind { id: i32, expected: &'static str },
+    #[error("no dialogue for speaker {0}")]
+    NoSpeaker(usize),
+    #[error("speaker {speaker} has no line {line}")]
+    NoLine { speaker: usize, line: usize },
+    #[error("{names} speaker names but {replicas} dialogue lists")]
+    Unpaired { names: usize, replicas: usize },
+    #[error("template {0:?} is not listed in the entity file")]
+    NoTemplate(String),
+    #[error("no entity made from template {0:?} to copy")]
+    NoPrototype(String),
+    #[error("{0:?} is not a perk")]
+    NotAPerk(String),
+    #[error("entity {id} is still linked from entity {by}")]
+    Linked { id: i32, by: i32 },
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    #[error("entity {from} links {property:?} to slot {target}, past the entity table")]
+    Dangling {
+        from: i32,
+        property: String,
+        target: u16,
+    },
+    #[error("entity {from} links {property:?} to slot {target}, which has no data")]
+    Empty {
+        from: i32,
+        property: String,
+        target: u16,
+    },
+}
+
+pub trait ResultExt {
+    fn within(self, name: &'static str) -> Self;
+    fn at(self, index: usize) -> Self;
+}
+
+impl<T> ResultExt for Result<T, ParseError> {
+    fn within(self, name: &'static str) -> Self {
+        self.map_err(|e| e.within(PathSegment::Name(name)))
+    }
+
+    fn at(self, index: usize) -> Self {
+        self.map_err(|e| e.within(PathSegment::Index(index)))
+    }
 }
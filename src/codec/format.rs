@@ -1,3 +1,3 @@
-pub fn fmt_blob(v: &Vec<u8>) -> String {
+pub fn fmt_blob(v: &[u8]) -> String {
     format!("blob {{len: {}}}", v.len())
 }
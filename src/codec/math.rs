@@ -0,0 +1,100 @@
+//! Geometry and colour values stored in entity properties.
+
+use crate::codec::Encodable;
+
+/// 3x4 transform: twelve floats, read as four rows of three.
+///
+/// The layout is assumed, not verified against a save: rows 0-2 are taken as the
+/// rotation and row 3 as the position, as in the usual row-major 4x3 matrix. The
+/// accessors below rely on it, while `rows` always round-trips as read.
+#[derive(Debug, Clone, Copy, PartialEq, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::float_rows"))]
+    pub rows: [[f32; 3]; 4],
+}
+
+impl Frame {
+    pub const IDENTITY: Frame = Frame {
+        rows: [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, 0.0],
+        ],
+    };
+
+    pub fn position(&self) -> [f32; 3] {
+        self.rows[3]
+    }
+
+    pub fn set_position(&mut self, position: [f32; 3]) {
+        self.rows[3] = position;
+    }
+
+    /// Moves by `delta`, keeping the rotation.
+    pub fn translate(&mut self, delta: [f32; 3]) {
+        for (p, d) in self.rows[3].iter_mut().zip(delta) {
+            *p += d;
+        }
+    }
+
+    pub fn rotation(&self) -> [[f32; 3]; 3] {
+        [self.rows[0], self.rows[1], self.rows[2]]
+    }
+
+    pub fn set_rotation(&mut self, rotation: [[f32; 3]; 3]) {
+        self.rows[..3].copy_from_slice(&rotation);
+    }
+}
+
+impl From<[f32; 12]> for Frame {
+    fn from(v: [f32; 12]) -> Self {
+        Frame {
+            rows: std::array::from_fn(|row| std::array::from_fn(|col| v[row * 3 + col])),
+        }
+    }
+}
+
+impl From<Frame> for [f32; 12] {
+    fn from(frame: Frame) -> Self {
+        std::array::from_fn(|i| frame.rows[i / 3][i % 3])
+    }
+}
+
+/// Rectangle: four u32s.
+///
+/// The field order is assumed, not verified against a save: left, top, right, bottom
+/// with the right and bottom edges exclusive, as in a Windows `RECT`. `width`, `height`
+/// and `contains` rely on it, while the fields always round-trip as read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rect {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl Rect {
+    pub fn width(&self) -> u32 {
+        self.right.saturating_sub(self.left)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.bottom.saturating_sub(self.top)
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.left..self.right).contains(&x) && (self.top..self.bottom).contains(&y)
+    }
+}
+
+/// RGB colour, a full 32 bit value per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: u32,
+    pub g: u32,
+    pub b: u32,
+}
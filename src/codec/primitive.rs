@@ -1,4 +1,4 @@
-use crate::codec::error::ParseError;
+use crate::codec::error::{EditError, ParseError};
 use crate::codec::stream::Stream;
 use crate::codec::Encodable;
 use byteorder::{LittleEndian, WriteBytesExt};
@@ -6,7 +6,8 @@ use encoding_rs::{UTF_16LE, WINDOWS_1251};
 use std::io::Write;
 use std::ops::{Deref, Shr};
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FOTString {
     Ascii(String),
     Win1251(String),
@@ -26,11 +27,27 @@ impl Deref for FOTString {
 }
 
 impl FOTString {
+    /// Bytes taken by the written string, length prefix included.
     pub fn serialized_length(&self) -> usize {
-        (match self {
-            FOTString::Ascii(data) => data.len(),
-            FOTString::Utf16(data) | FOTString::Win1251(data) => data.len() * 2,
-        }) + 4
+        let mut buf = Vec::new();
+        self.write(&mut buf).expect("writing to a Vec can't fail");
+        buf.len()
+    }
+
+    /// Replaces the text, switching from ASCII to Windows-1251 when needed.
+    /// Wide strings are read back one byte per character, so anything outside
+    /// Windows-1251 is rejected.
+    pub fn set(&mut self, value: &str) -> Result<(), EditError> {
+        if matches!(self, FOTString::Ascii(_)) && value.is_ascii() {
+            *self = FOTString::Ascii(value.to_owned());
+            return Ok(());
+        }
+        let (_, _, err) = WINDOWS_1251.encode(value);
+        if err {
+            return Err(EditError::Unencodable(value.to_owned()));
+        }
+        *self = FOTString::Win1251(value.to_owned());
+        Ok(())
     }
 }
 
@@ -1,35 +1,79 @@
 use crate::assert_section;
-use crate::codec::error::ParseError;
+use crate::codec::error::{ParseError, ResultExt};
 use crate::codec::primitive::FOTString;
 use crate::codec::stream::Stream;
 use crate::codec::Encodable;
 use derive_debug::Dbg;
-use std::io::{Error, Read, Write};
+use std::ffi::CString;
+use std::io::{Error, ErrorKind, Write};
 
 const HEADER: &str = "<campaign>\0";
+// world_file always starts this far after the header, the magic included
+const BLOCK_LEN: usize = 0x22BA;
 
+/// Campaign progress. Only `magic` and `world_file` are decoded so far, the mission
+/// list, map state and variables are somewhere in `unknown` and `tail`.
 #[derive(Dbg)]
-pub struct Campaign<'a> {
-    #[dbg(placeholder = "...")]
-    pub raw: &'a [u8],
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Campaign {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
+    pub magic: CString,
+    /// Rest of the fixed block in front of `world_file`, not decoded yet.
+    /// Together with `magic` it always takes 0x22BA bytes.
+    #[dbg(formatter = "crate::codec::format::fmt_blob")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+    pub unknown: Vec<u8>,
     pub world_file: FOTString,
+    /// Not decoded yet, kept as is.
+    #[dbg(formatter = "crate::codec::format::fmt_blob")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+    pub tail: Vec<u8>,
 }
 
-impl<'a> Encodable<'a> for Campaign<'a> {
+// the fixed block has a variable length magic in front, so this one is written by hand
+impl<'a> Encodable<'a> for Campaign {
     fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let d = data.clone().read_slice(data.remain())?;
-        //let _magic = data.read_cstr()?;
-        data.skip(0x22BA)?;
-        let world_file = data.read_string()?;
-        data.remain();
-        Ok(Self { raw: d, world_file })
+        data.section("campaign", |data| {
+            assert_section!(data, HEADER);
+            let start = data.pos();
+            let magic = CString::parse(data)?;
+            let len = BLOCK_LEN.checked_sub(data.pos() - start).ok_or_else(|| {
+                ParseError::InvalidValue {
+                    offset: start,
+                    expected: format!("magic shorter than {} bytes", BLOCK_LEN),
+                    found: format!("{} bytes", data.pos() - start),
+                }
+            })?;
+            let unknown = data.read_slice(len).within("unknown")?.to_vec();
+            let world_file = FOTString::parse(data).within("world_file")?;
+            let tail = data.read_slice(data.remain())?.to_vec();
+
+            Ok(Self {
+                magic,
+                unknown,
+                world_file,
+                tail,
+            })
+        })
     }
 
     fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        let magic = self.magic.as_bytes_with_nul();
+        if magic.len() + self.unknown.len() != BLOCK_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "magic and unknown must take {} bytes, take {}",
+                    BLOCK_LEN,
+                    magic.len() + self.unknown.len()
+                ),
+            ));
+        }
         stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.raw)?;
-
+        stream.write_all(magic)?;
+        stream.write_all(&self.unknown)?;
+        self.world_file.write(&mut stream)?;
+        stream.write_all(&self.tail)?;
         Ok(())
     }
 }
@@ -1,55 +1,29 @@
-use crate::assert_section;
-use crate::codec::error::ParseError;
 use crate::codec::primitive::FOTString;
-use crate::codec::stream::Stream;
 use crate::codec::Encodable;
-use byteorder::{LittleEndian, WriteBytesExt};
-use std::ffi::CStr;
-use std::io::{Error, Read, Write};
-
-const HEADER: &str = "<campaign_save>\0";
-
-#[derive(Debug)]
-pub struct CampaignSave<'a> {
-    pub magic: &'a CStr,
+use std::borrow::Cow;
+use std::ffi::CString;
+use std::path::Path;
+
+#[derive(Debug, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "campaign_save")]
+pub struct CampaignSave {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
+    pub magic: CString,
     pub files: Vec<CampaignFile>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Encodable)]
 pub struct CampaignFile {
     pub path: FOTString,
     pub data: Vec<u8>,
 }
 
-impl<'a> Encodable<'a> for CampaignFile {
-    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        let path = FOTString::parse(data)?;
-        let len = data.read_u32()?;
-        let data = data.read_slice(len as _)?.to_vec();
-        Ok(CampaignFile { path, data })
-    }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        self.path.write(&mut stream)?;
-        stream.write_u32::<LittleEndian>(self.data.len() as _)?;
-        stream.write_all(&self.data)?;
-        Ok(())
-    }
-}
-
-impl<'a> Encodable<'a> for CampaignSave<'a> {
-    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?;
-
-        let files = <_>::parse(data)?;
-        Ok(Self { magic, files })
-    }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.magic.to_bytes_with_nul())?;
-        self.files.write(stream)?;
-        Ok(())
+impl CampaignFile {
+    pub fn extension(&self) -> Cow<'_, str> {
+        Path::new(&*self.path)
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
     }
 }
@@ -1,34 +1,12 @@
-use crate::assert_section;
-use crate::codec::error::ParseError;
 use crate::codec::primitive::FOTString;
-use crate::codec::stream::Stream;
 use crate::codec::Encodable;
 use std::ffi::CString;
-use std::io::{Error, Read, Write};
 
-const HEADER: &str = "<entity_file>\0";
-
-#[derive(Debug)]
+#[derive(Debug, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "entity_file")]
 pub struct EntityFile {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
     pub magic: CString,
     pub data: Vec<FOTString>,
 }
-
-impl<'a> Encodable<'a> for EntityFile {
-    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?.to_owned();
-
-        Ok(Self {
-            magic,
-            data: <_>::parse(data)?,
-        })
-    }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.magic.to_bytes_with_nul())?;
-        self.data.write(stream)?;
-        Ok(())
-    }
-}
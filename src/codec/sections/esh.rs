@@ -1,58 +1,154 @@
-use crate::assert_section;
 use crate::codec::error::ParseError;
+use crate::codec::math::{Color, Frame, Rect};
 use crate::codec::primitive::FOTString;
 use crate::codec::stream::Stream;
 use crate::codec::Encodable;
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{LittleEndian, WriteBytesExt};
 use derive_debug::Dbg;
 use std::ffi::CString;
-use std::io::{Error, Read, Write};
+use std::io::{Error, Write};
 
-const HEADER: &str = "<esh>\0";
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "esh")]
 pub struct Esh {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
     pub magic: CString,
     pub values: Vec<EshEntry>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EshEntry {
     pub name: FOTString,
     pub value: EshValue,
 }
 
-#[derive(Dbg)]
+impl Esh {
+    /// First property with the given name.
+    pub fn get(&self, name: &str) -> Option<&EshValue> {
+        self.values
+            .iter()
+            .find(|e| &*e.name == name)
+            .map(|e| &e.value)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut EshValue> {
+        self.values
+            .iter_mut()
+            .find(|e| &*e.name == name)
+            .map(|e| &mut e.value)
+    }
+
+    /// Replaces the value in place, or appends a new property when there is none.
+    pub fn set(&mut self, name: &str, value: EshValue) {
+        match self.get_mut(name) {
+            Some(v) => *v = value,
+            None => self.values.push(EshEntry {
+                name: FOTString::Ascii(name.to_owned()),
+                value,
+            }),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<EshValue> {
+        let i = self.values.iter().position(|e| &*e.name == name)?;
+        Some(self.values.remove(i).value)
+    }
+
+    /// Value of the `Type` property, which names the entity kind.
+    pub fn type_name(&self) -> Option<&str> {
+        match self.get("Type")? {
+            EshValue::Type(t) => Some(t),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Dbg, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum EshValue {
     Bool(bool),
-    Float(f32),
+    Float(#[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::float"))] f32),
     I32(i32),
     String(FOTString),
-    Color(#[dbg(placeholder = "...")] [u32; 3]),
+    Color(Color),
 
     Sprite(FOTString),
     Type(FOTString),
 
-    Bin(#[dbg(formatter = "crate::codec::format::fmt_blob")] Vec<u8>),
+    Bin(
+        #[dbg(formatter = "crate::codec::format::fmt_blob")]
+        #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+        Vec<u8>,
+    ),
     Link {
         flags: u16,
         entity: u16,
     },
-    Frame([f32; 12]),
-    Rect([u32; 4]),
+    Frame(Frame),
+    Rect(Rect),
 
     ZoneName(FOTString),
 
+    /// Tags not decoded yet (6, 7, 10, 15-20 and above 21), and decoded tags whose payload
+    /// failed to parse or had the wrong length, see [`EshValue::is_decoded`] and
+    /// [`crate::codec::census`].
     Unknown(
         u32,
-        #[dbg(formatter = "crate::codec::format::fmt_blob")] Vec<u8>,
+        #[dbg(formatter = "crate::codec::format::fmt_blob")]
+        #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+        Vec<u8>,
     ),
 }
 
-impl<'a> Encodable<'a> for EshValue {
-    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        let t = data.read_u32()?;
-        let data_len = data.read_u32()? as usize;
+impl EshValue {
+    fn tag(&self) -> u32 {
+        match self {
+            EshValue::Bool(_) => 1,
+            EshValue::Float(_) => 2,
+            EshValue::I32(_) => 3,
+            EshValue::String(_) => 4,
+            EshValue::Color(_) => 5,
+            EshValue::Sprite(_) => 8,
+            EshValue::Type(_) => 9,
+            EshValue::Bin(_) => 11,
+            EshValue::Link { .. } => 12,
+            EshValue::Frame(_) => 13,
+            EshValue::Rect(_) => 14,
+            EshValue::ZoneName(_) => 21,
+            EshValue::Unknown(t, _) => *t,
+        }
+    }
+
+    fn write_payload<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        match self {
+            EshValue::Bool(b) => stream.write_u8(*b as _),
+            EshValue::Float(data) => data.write(stream),
+            EshValue::I32(data) => data.write(stream),
+            EshValue::String(data)
+            | EshValue::Sprite(data)
+            | EshValue::Type(data)
+            | EshValue::ZoneName(data) => data.write(stream),
+            EshValue::Color(data) => data.write(stream),
+            EshValue::Bin(data) | EshValue::Unknown(_, data) => stream.write_all(data),
+            EshValue::Link { flags, entity } => {
+                stream.write_u16::<LittleEndian>(*entity)?;
+                stream.write_u16::<LittleEndian>(*flags)
+            }
+            EshValue::Frame(data) => data.write(stream),
+            EshValue::Rect(data) => data.write(stream),
+        }
+    }
+}
+
+impl EshValue {
+    /// Whether the tag has a variant, so an `Unknown` with it holds a payload that didn't match.
+    pub fn is_decoded(tag: u32) -> bool {
+        matches!(tag, 1..=5 | 8 | 9 | 11..=14 | 21)
+    }
+
+    fn parse_payload(t: u32, data: &mut Stream) -> Result<Self, ParseError> {
         Ok(match t {
             1 => EshValue::Bool(data.read_i8()? != 0),
             2 => EshValue::Float(<_>::parse(data)?),
@@ -63,11 +159,11 @@ impl<'a> Encodable<'a> for EshValue {
             8 => EshValue::Sprite(<_>::parse(data)?),
             9 => EshValue::Type(<_>::parse(data)?),
 
-            11 => EshValue::Bin(data.read_slice(data_len)?.to_vec()),
+            11 => EshValue::Bin(data.read_slice(data.remain())?.to_vec()),
 
             12 => {
-                let entity = data.read_u16::<LittleEndian>()?;
-                let flags = data.read_u16::<LittleEndian>()?;
+                let entity = data.read_u16()?;
+                let flags = data.read_u16()?;
                 EshValue::Link { flags, entity }
             }
 
@@ -77,114 +173,31 @@ impl<'a> Encodable<'a> for EshValue {
 
             21 => EshValue::ZoneName(<_>::parse(data)?),
 
-            t => EshValue::Unknown(t, data.read_slice(data_len)?.to_vec()),
+            t => EshValue::Unknown(t, data.read_slice(data.remain())?.to_vec()),
         })
     }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        match self {
-            EshValue::Bool(b) => {
-                stream.write_u32::<LittleEndian>(1)?;
-                stream.write_u32::<LittleEndian>(1)?;
-                stream.write_u8(*b as _)?;
-            }
-            EshValue::Float(data) => {
-                stream.write_u32::<LittleEndian>(2)?;
-                stream.write_u32::<LittleEndian>(4)?;
-                stream.write_f32::<LittleEndian>(*data)?;
-            }
-            EshValue::I32(data) => {
-                stream.write_u32::<LittleEndian>(3)?;
-                stream.write_u32::<LittleEndian>(4)?;
-                stream.write_i32::<LittleEndian>(*data)?;
-            }
-            EshValue::String(data) => {
-                stream.write_u32::<LittleEndian>(4)?;
-                stream.write_u32::<LittleEndian>(data.serialized_length() as _)?;
-                data.write(stream)?;
-            }
-            EshValue::Color(data) => {
-                stream.write_u32::<LittleEndian>(5)?;
-                stream.write_u32::<LittleEndian>(12)?;
-                data.write(stream)?;
-            }
-
-            EshValue::Sprite(data) => {
-                stream.write_u32::<LittleEndian>(8)?;
-                stream.write_u32::<LittleEndian>(data.serialized_length() as _)?;
-                data.write(stream)?;
-            }
-            EshValue::Type(data) => {
-                stream.write_u32::<LittleEndian>(9)?;
-                stream.write_u32::<LittleEndian>(data.serialized_length() as _)?;
-                data.write(stream)?;
-            }
-            EshValue::Bin(data) => {
-                stream.write_u32::<LittleEndian>(11)?;
-                stream.write_u32::<LittleEndian>(data.len() as _)?;
-                stream.write_all(data)?;
-            }
-            EshValue::Link { flags, entity } => {
-                stream.write_u32::<LittleEndian>(12)?;
-                stream.write_u32::<LittleEndian>(4)?;
-                stream.write_u16::<LittleEndian>(*entity)?;
-                stream.write_u16::<LittleEndian>(*flags)?;
-            }
-            EshValue::Frame(data) => {
-                stream.write_u32::<LittleEndian>(13)?;
-                stream.write_u32::<LittleEndian>(48)?;
-                data.write(stream)?;
-            }
-            EshValue::Rect(data) => {
-                stream.write_u32::<LittleEndian>(14)?;
-                stream.write_u32::<LittleEndian>(16)?;
-                data.write(stream)?;
-            }
-            EshValue::ZoneName(data) => {
-                stream.write_u32::<LittleEndian>(21)?;
-                stream.write_u32::<LittleEndian>(data.serialized_length() as _)?;
-                data.write(stream)?;
-            }
-            EshValue::Unknown(t, data) => {
-                stream.write_u32::<LittleEndian>(*t)?;
-                stream.write_u32::<LittleEndian>(data.len() as _)?;
-                stream.write_all(data)?;
-            }
-        }
-        Ok(())
-    }
 }
 
-impl<'a> Encodable<'a> for EshEntry {
+impl<'a> Encodable<'a> for EshValue {
+    // the payload is parsed on its own, and one that fails or doesn't take up exactly
+    // data_len bytes is kept as Unknown, so a type whose layout differs in some save
+    // version can't desync the stream
     fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        let name = FOTString::parse(data)?;
-        let value = EshValue::parse(data)?;
-
-        Ok(EshEntry { name, value })
-    }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        self.name.write(&mut stream)?;
-        self.value.write(&mut stream)?;
-        Ok(())
-    }
-}
-
-impl<'a> Encodable<'a> for Esh {
-    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?.to_owned();
-
-        let values = (0..data.read_u32()?)
-            .map(|_| -> Result<_, ParseError> { EshEntry::parse(data) })
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(Self { magic, values })
+        let t = data.read_u32()?;
+        let data_len = data.read_u32()? as usize;
+        let payload = data.read_slice(data_len)?;
+        let mut sub = Stream::new(payload);
+        match Self::parse_payload(t, &mut sub) {
+            Ok(value) if sub.remain() == 0 => Ok(value),
+            _ => Ok(EshValue::Unknown(t, payload.to_vec())),
+        }
     }
 
     fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.magic.to_bytes_with_nul())?;
-        self.values.write(stream)?;
-        Ok(())
+        let mut payload = Vec::new();
+        self.write_payload(&mut payload)?;
+        stream.write_u32::<LittleEndian>(self.tag())?;
+        stream.write_u32::<LittleEndian>(payload.len() as _)?;
+        stream.write_all(&payload)
     }
 }
@@ -1,48 +1,199 @@
-use crate::assert_section;
-use crate::codec::error::ParseError;
+use crate::codec::error::EditError;
 use crate::codec::primitive::FOTString;
 use crate::codec::sections::zar::Zar;
-use crate::codec::stream::Stream;
 use crate::codec::Encodable;
-use byteorder::{ReadBytesExt, WriteBytesExt};
 use derive_debug::Dbg;
-use std::ffi::CStr;
-use std::io::{Error, Read, Write};
+use std::ffi::CString;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
 
-const HEADER: &str = "<saveh>\0";
-
-#[derive(Dbg)]
-pub struct Saveh<'a> {
-    pub magic: &'a CStr,
+/// Header shown on the load game screen. Prefer the named accessors below
+/// over indexing `strings` and `ints` directly.
+///
+/// Which string and int holds what is inferred, so the named setters, which would
+/// rename the wrong field in players' saves on a wrong guess, are only built with the
+/// `unstable` feature. Difficulty, play time and in-game time are not located yet.
+#[derive(Dbg, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "saveh")]
+pub struct Saveh {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
+    pub magic: CString,
     pub version: i8,
     pub strings: [FOTString; 5],
+    /// Thumbnails, see [`Zar::to_rgba`].
     pub tmp: [Zar; 8],
+    /// Save time, see [`Saveh::saved_at`].
     #[dbg(placeholder = "...")]
     pub ints: [u32; 6],
 }
 
-impl<'a> Encodable<'a> for Saveh<'a> {
-    fn parse(data: &mut Stream<'a>) -> Result<Saveh<'a>, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?;
-        let some_ver = data.read_i8()?;
-
-        Ok(Saveh {
-            magic,
-            version: some_ver,
-            strings: <_>::parse(data)?,
-            tmp: <_>::parse(data)?,
-            ints: <_>::parse(data)?,
+// meaning of `strings`, inferred from the order the load game screen shows them in
+// and not confirmed against the game, the fifth string is not known
+const TITLE: usize = 0;
+const CAMPAIGN: usize = 1;
+const MISSION: usize = 2;
+const PLAYER: usize = 3;
+const UNKNOWN: usize = 4;
+
+fn days_in_month(year: u32, month: u32) -> u32 {
+    match month {
+        2 if year.is_multiple_of(4) && (!year.is_multiple_of(100) || year.is_multiple_of(400)) => {
+            29
+        }
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Wall clock time the save was made, stored in `Saveh::ints` as year, month, day,
+/// hour, minute and second. That order is assumed from the values seen there,
+/// [`Saveh::saved_at`] gives `None` for anything that isn't a valid date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SaveTime {
+    year: u32,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl SaveTime {
+    pub fn new(
+        year: u32,
+        month: u32,
+        day: u32,
+        hour: u32,
+        minute: u32,
+        second: u32,
+    ) -> Result<Self, EditError> {
+        let check = |what, value, range: RangeInclusive<u32>| {
+            if range.contains(&value) {
+                Ok(value)
+            } else {
+                Err(EditError::OutOfRange {
+                    what,
+                    value: value as i64,
+                })
+            }
+        };
+        let year = check("year", year, 0..=9999)?;
+        let month = check("month", month, 1..=12)?;
+        Ok(Self {
+            year,
+            month,
+            day: check("day", day, 1..=days_in_month(year, month))?,
+            hour: check("hour", hour, 0..=23)?,
+            minute: check("minute", minute, 0..=59)?,
+            second: check("second", second, 0..=59)?,
         })
     }
 
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.magic.to_bytes_with_nul())?;
-        stream.write_i8(self.version)?;
-        self.strings.write(&mut stream)?;
-        self.tmp.write(&mut stream)?;
-        self.ints.write(&mut stream)?;
-        Ok(())
+    pub fn year(&self) -> u32 {
+        self.year
+    }
+
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+
+    pub fn hour(&self) -> u32 {
+        self.hour
+    }
+
+    pub fn minute(&self) -> u32 {
+        self.minute
+    }
+
+    pub fn second(&self) -> u32 {
+        self.second
+    }
+}
+
+impl Display for SaveTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+impl Saveh {
+    /// Save format version.
+    pub fn version(&self) -> i8 {
+        self.version
+    }
+
+    pub fn title(&self) -> &str {
+        &self.strings[TITLE]
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_title(&mut self, title: &str) -> Result<(), EditError> {
+        self.strings[TITLE].set(title)
+    }
+
+    pub fn campaign(&self) -> &str {
+        &self.strings[CAMPAIGN]
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_campaign(&mut self, campaign: &str) -> Result<(), EditError> {
+        self.strings[CAMPAIGN].set(campaign)
+    }
+
+    /// Mission (map) the save was made on.
+    pub fn mission(&self) -> &str {
+        &self.strings[MISSION]
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_mission(&mut self, mission: &str) -> Result<(), EditError> {
+        self.strings[MISSION].set(mission)
+    }
+
+    pub fn player_name(&self) -> &str {
+        &self.strings[PLAYER]
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_player_name(&mut self, name: &str) -> Result<(), EditError> {
+        self.strings[PLAYER].set(name)
+    }
+
+    /// Fifth string of the header, meaning not known yet.
+    pub fn unknown_string(&self) -> &str {
+        &self.strings[UNKNOWN]
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_unknown_string(&mut self, value: &str) -> Result<(), EditError> {
+        self.strings[UNKNOWN].set(value)
+    }
+
+    /// `None` when `ints` don't hold a valid date, e.g. in saves made by other tools.
+    pub fn saved_at(&self) -> Option<SaveTime> {
+        let [year, month, day, hour, minute, second] = self.ints;
+        SaveTime::new(year, month, day, hour, minute, second).ok()
+    }
+
+    #[cfg(feature = "unstable")]
+    pub fn set_saved_at(&mut self, time: SaveTime) {
+        self.ints = [
+            time.year,
+            time.month,
+            time.day,
+            time.hour,
+            time.minute,
+            time.second,
+        ];
     }
 }
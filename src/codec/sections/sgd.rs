@@ -1,44 +1,19 @@
-use crate::assert_section;
-use crate::codec::error::ParseError;
 use crate::codec::primitive::FOTString;
-use crate::codec::stream::Stream;
 use crate::codec::Encodable;
 use std::ffi::CString;
-use std::io::{Error, Read, Write};
 
-const HEADER: &str = "<sgd>\0";
-
-#[derive(Debug)]
+/// Dialogue of the map, see [`crate::model::dialogue`].
+#[derive(Debug, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "sgd")]
 pub struct SDG {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
     pub magic: CString,
+    /// Layout not known yet, kept as is.
+    #[encodable(count = 0x48)]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
     pub unknown: Vec<u8>,
+    /// Speakers, paired with `replicas` by index.
     pub names: Vec<FOTString>,
     pub replicas: Vec<Vec<FOTString>>,
 }
-
-impl<'a> Encodable<'a> for SDG {
-    fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?.to_owned();
-        let unknown = data.read_slice(0x48)?.to_vec();
-
-        let names = <Vec<FOTString>>::parse(data)?;
-        let replicas = <Vec<Vec<FOTString>>>::parse(data)?;
-
-        Ok(Self {
-            magic,
-            unknown,
-            names,
-            replicas,
-        })
-    }
-
-    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
-        stream.write_all(HEADER.as_bytes())?;
-        stream.write_all(self.magic.to_bytes_with_nul())?;
-        stream.write_all(&self.unknown)?;
-        self.names.write(&mut stream)?;
-        self.replicas.write(&mut stream)?;
-        Ok(())
-    }
-}
@@ -1,5 +1,5 @@
 use crate::assert_section;
-use crate::codec::error::ParseError;
+use crate::codec::error::{ParseError, SectionPath};
 use crate::codec::primitive::FOTString;
 use crate::codec::sections::sgd::SDG;
 use crate::codec::sections::ssg::SSG;
@@ -7,54 +7,187 @@ use crate::codec::stream::Stream;
 use crate::codec::Encodable;
 use byteorder::{LittleEndian, WriteBytesExt};
 use derive_debug::Dbg;
-use flate2::{Compression, FlushCompress, FlushDecompress};
-use std::ffi::CStr;
+use flate2::bufread::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use std::borrow::Cow;
+use std::ffi::CString;
 use std::io::{Error, Read, Write};
 
 const HEADER: &str = "<world>\0";
+/// Largest inflated payload accepted. Maps take a few MB, the limit only keeps a crafted
+/// stream from inflating to the 4 GiB its length field allows.
+pub const MAX_PAYLOAD_LEN: usize = 64 << 20;
+
+/// What the second length in the `<world>` header holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SecondLength {
+    Uncompressed,
+    Compressed,
+    Other(u32),
+}
 
 #[derive(Dbg)]
-pub struct World<'a> {
-    pub magic: &'a CStr,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct World {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
+    pub magic: CString,
     pub path: FOTString,
     pub sdg: SDG,
     pub ssg: SSG,
+    /// Rest of the payload after `ssg`, not decoded yet, kept as is.
     #[dbg(formatter = "crate::codec::format::fmt_blob")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
     pub tail: Vec<u8>,
+    pub second_length: SecondLength,
+    /// Level used when the payload has to be recompressed, taken from the original zlib header.
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::compression"))]
+    pub compression: Compression,
+    /// Compressed stream as it was read, written back as is while the payload is unchanged.
+    /// Set it to `None` to leave it out of exported JSON, the payload is then recompressed
+    /// on import and may not match the stream of another zlib encoder.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub original: Option<Original>,
+}
+
+/// Compressed payload of a `World` as read, with what is needed to tell
+/// whether the payload changed since without inflating it again.
+#[derive(Dbg, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Original {
+    #[dbg(formatter = "crate::codec::format::fmt_blob")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+    pub compressed: Vec<u8>,
+    /// Length of the inflated payload.
+    pub len: usize,
+    /// CRC-32 of the inflated payload.
+    pub crc: u32,
+}
+
+impl Original {
+    fn matches(&self, payload: &[u8]) -> bool {
+        let mut crc = Crc::new();
+        crc.update(payload);
+        self.len == payload.len() && self.crc == crc.sum()
+    }
 }
 
-impl<'a> Encodable<'a> for World<'a> {
+// returns inflated data and the number of compressed bytes consumed,
+// the buffer grows as data is actually inflated instead of trusting the header
+fn inflate(data: &[u8], len: usize) -> Result<(Vec<u8>, usize), String> {
+    let mut decoder = ZlibDecoder::new(data);
+    let mut res = Vec::new();
+    let extra = decoder
+        .by_ref()
+        .take(len as u64)
+        .read_to_end(&mut res)
+        .and_then(|_| decoder.read(&mut [0]))
+        .map_err(|e| e.to_string())?;
+    if extra != 0 {
+        return Err(format!("more than {} inflated bytes", len));
+    }
+    if res.len() != len {
+        return Err(format!("{} inflated bytes", res.len()));
+    }
+    Ok((res, decoder.total_in() as _))
+}
+
+// FLEVEL bits of the zlib header only tell the level class, map each to its zlib level
+fn header_level(data: &[u8]) -> Compression {
+    match data.get(1).map(|flg| flg >> 6) {
+        Some(0) => Compression::fast(),
+        Some(1) => Compression::new(5),
+        Some(3) => Compression::best(),
+        _ => Compression::default(),
+    }
+}
+
+// the inflated payload: map path, dialogue, entities and the undecoded rest
+fn parse_payload(data: &[u8]) -> Result<(FOTString, SDG, SSG, Vec<u8>), ParseError> {
+    let mut stream = Stream::new(data);
+    let path = FOTString::parse(&mut stream)?; // HEADER
+    let sdg = SDG::parse(&mut stream)?;
+    let ssg = SSG::parse(&mut stream)?;
+    let tail = stream.read_slice(stream.remain())?.to_vec();
+    Ok((path, sdg, ssg, tail))
+}
+
+/// Inflated payload of the world whose zlib stream starts at `start`,
+/// right after the two lengths of the header.
+pub(crate) fn payload_at(data: &[u8], start: usize) -> Option<Vec<u8>> {
+    let len = data.get(start.checked_sub(8)?..start - 4)?;
+    let len = u32::from_le_bytes(len.try_into().ok()?) as usize;
+    if len > MAX_PAYLOAD_LEN {
+        return None;
+    }
+    inflate(data.get(start..)?, len)
+        .ok()
+        .map(|(payload, _)| payload)
+}
+
+/// Section path inside the payload of the value that reads the byte at `offset`,
+/// see [`crate::codec::verify::section_at`].
+pub(crate) fn payload_section_at(payload: &[u8], offset: usize) -> SectionPath {
+    match parse_payload(&payload[..offset.min(payload.len())]) {
+        Err(e) => e.path(),
+        Ok(_) => SectionPath::default(),
+    }
+}
+
+impl<'a> Encodable<'a> for World {
     fn parse(data: &mut Stream<'a>) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?;
-
-        let uncompressed_length = data.read_u32()? as usize;
-        data.read_u32()?; // second len
-
-        let mut result = vec![0; uncompressed_length];
-        let mut decomp = flate2::Decompress::new(true);
-        decomp
-            .decompress(
-                data.clone().read_slice(data.len() - data.pos())?,
-                &mut result,
-                FlushDecompress::Finish,
-            )
-            .unwrap();
-        data.skip(decomp.total_in() as _)?;
-
-        let world_data = result;
-
-        let mut stream = Stream::new(&world_data);
-        let path = FOTString::parse(&mut stream)?; // HEADER
-        let sdg = SDG::parse(&mut stream)?;
-        let ssg = SSG::parse(&mut stream)?;
-
-        Ok(Self {
-            magic,
-            path,
-            sdg,
-            ssg,
-            tail: stream.read_slice(stream.len() - stream.pos())?.to_vec(),
+        data.section("world", |data| {
+            assert_section!(data, HEADER);
+            let magic = data.read_cstr()?.to_owned();
+
+            let uncompressed_length = data.read_u32()? as usize;
+            let second_length = data.read_u32()?;
+
+            let offset = data.pos();
+            if uncompressed_length > MAX_PAYLOAD_LEN {
+                return Err(ParseError::InvalidValue {
+                    offset: offset - 8,
+                    expected: format!("payload of at most {} bytes", MAX_PAYLOAD_LEN),
+                    found: format!("{} bytes", uncompressed_length),
+                });
+            }
+            let (world_data, compressed_length) =
+                inflate(data.clone().read_slice(data.remain())?, uncompressed_length).map_err(
+                    |found| ParseError::InvalidValue {
+                        offset,
+                        expected: format!("zlib stream of {} bytes", uncompressed_length),
+                        found,
+                    },
+                )?;
+            let original = data.read_slice(compressed_length)?;
+
+            let second_length = match second_length as usize {
+                l if l == uncompressed_length => SecondLength::Uncompressed,
+                l if l == compressed_length => SecondLength::Compressed,
+                _ => SecondLength::Other(second_length),
+            };
+
+            let mut crc = Crc::new();
+            crc.update(&world_data);
+            let original = Original {
+                compressed: original.to_vec(),
+                len: world_data.len(),
+                crc: crc.sum(),
+            };
+
+            let (path, sdg, ssg, tail) = parse_payload(&world_data)?;
+
+            Ok(Self {
+                magic,
+                path,
+                sdg,
+                ssg,
+                tail,
+                second_length,
+                compression: header_level(&original.compressed),
+                original: Some(original),
+            })
         })
     }
 
@@ -64,15 +197,25 @@ impl<'a> Encodable<'a> for World<'a> {
         self.sdg.write(&mut world_data)?;
         self.ssg.write(&mut world_data)?;
         world_data.extend_from_slice(&self.tail);
-        let mut result = Vec::with_capacity(world_data.len());
-        let mut comp = flate2::Compress::new(Compression::best(), true);
-        comp.compress_vec(&world_data, &mut result, FlushCompress::Finish)
-            .unwrap();
+
+        let unchanged = self.original.as_ref().filter(|o| o.matches(&world_data));
+        let result = match unchanged {
+            Some(orig) => Cow::Borrowed(&orig.compressed[..]),
+            None => {
+                let mut comp = ZlibEncoder::new(Vec::new(), self.compression);
+                comp.write_all(&world_data)?;
+                Cow::Owned(comp.finish()?)
+            }
+        };
 
         stream.write_all(HEADER.as_bytes())?;
         stream.write_all(self.magic.to_bytes_with_nul())?;
         stream.write_u32::<LittleEndian>(world_data.len() as _)?;
-        stream.write_u32::<LittleEndian>(world_data.len() as _)?;
+        stream.write_u32::<LittleEndian>(match self.second_length {
+            SecondLength::Uncompressed => world_data.len() as _,
+            SecondLength::Compressed => result.len() as _,
+            SecondLength::Other(l) => l,
+        })?;
         stream.write_all(&result)?;
         Ok(())
     }
@@ -2,26 +2,42 @@ use crate::assert_section;
 use crate::codec::error::ParseError;
 use crate::codec::stream::Stream;
 use crate::codec::Encodable;
-use std::io::{Error, Read, Write};
+use derive_debug::Dbg;
+use std::ffi::CString;
+use std::io::{Error, Write};
 
 const HEADER: &str = "<world_zone>\0";
 
-#[derive(Debug)]
+// body layout is not known yet, no sample carrying it was seen so far,
+// so everything after the magic is kept verbatim up to the end of the stream
+#[derive(Dbg)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WorldZone {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
+    pub magic: CString,
+    /// Not decoded yet, kept as is.
+    #[dbg(formatter = "crate::codec::format::fmt_blob")]
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
     pub data: Vec<u8>,
 }
 
 impl<'a> Encodable<'a> for WorldZone {
     fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        dbg!(data.read_cstr()?);
+        data.section("world_zone", |data| {
+            assert_section!(data, HEADER);
+            let magic = data.read_cstr()?.to_owned();
 
-        Ok(WorldZone {
-            data: data.read_slice(data.len() - data.pos())?.to_vec(),
+            Ok(WorldZone {
+                magic,
+                data: data.read_slice(data.remain())?.to_vec(),
+            })
         })
     }
 
-    fn write<T: Write>(&self, _stream: T) -> Result<(), Error> {
-        todo!()
+    fn write<T: Write>(&self, mut stream: T) -> Result<(), Error> {
+        stream.write_all(HEADER.as_bytes())?;
+        stream.write_all(self.magic.to_bytes_with_nul())?;
+        stream.write_all(&self.data)?;
+        Ok(())
     }
 }
@@ -1,59 +1,186 @@
-use crate::assert_section;
-use crate::codec::error::ParseError;
+use crate::codec::error::{ParseError, ResultExt};
 use crate::codec::stream::Stream;
 use crate::codec::Encodable;
-use byteorder::ReadBytesExt;
+use crate::image::RgbaImage;
 use derive_debug::Dbg;
 use std::ffi::CString;
-use std::io::Read;
-use std::io::{Error, Write};
+use std::io::{Error, ErrorKind};
 
-const HEADER: &str = "<zar>\0";
+// pixel data is a sequence of runs, the low two bits of the run byte are its type, the rest its length
+const RUN_TRANSPARENT: u8 = 0;
+const RUN_OPAQUE: u8 = 1;
+const RUN_TRANSLUCENT: u8 = 2;
+const RUN_SHADOW: u8 = 3;
+const MAX_RUN: usize = 0x3f;
 
-#[derive(Dbg)]
+#[derive(Dbg, Clone, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[encodable(section = "zar")]
 pub struct Zar {
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::cstring"))]
     pub magic: CString,
     pub h: i32,
     pub w: i32,
-    pub data: Option<ZarSub>,
+    /// Non-zero when `palette` follows, kept as read so other values survive a re-save.
+    pub present: u8,
+    /// Absent for images made only of transparent and shadow runs.
+    #[encodable(when = "*present != 0")]
+    pub palette: Option<ZarPalette>,
+    /// Run-length encoded pixels, see [`Zar::to_rgba`].
     #[dbg(formatter = "crate::codec::format::fmt_blob")]
-    pub unknown: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::codec::ser::hex"))]
+    pub runs: Vec<u8>,
 }
 
-#[derive(Dbg)]
-pub struct ZarSub {
+#[derive(Dbg, Clone, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZarPalette {
+    /// Colors as `0xAARRGGBB`, the alpha byte is not used.
     #[dbg(placeholder = "...")]
-    pub img: Vec<i32>,
-    pub flag: u8,
+    pub colors: Vec<i32>,
+    /// Index of the color used by shadow runs.
+    pub shadow: u8,
 }
 
-impl<'a> Encodable<'a> for Zar {
-    #[allow(clippy::size_of_in_element_count)]
-    fn parse(data: &mut Stream) -> Result<Self, ParseError> {
-        assert_section!(data, HEADER);
-        let magic = data.read_cstr()?.to_owned();
-        let h = data.read_i32()?;
-        let w = data.read_i32()?;
-        let flag = data.read_u8()?;
-        let opt = if flag != 0 {
-            let img = <Vec<i32>>::parse(data)?;
-            let flag = data.read_u8()?;
-            Some(ZarSub { img, flag })
-        } else {
-            None
+impl Zar {
+    /// Decodes the pixel runs into `w * h` RGBA pixels.
+    ///
+    /// Runs are transparent (no data), opaque (a palette index per pixel),
+    /// translucent (index and alpha per pixel) or shadow (alpha per pixel, color `shadow`).
+    /// Shadow runs are black when there is no palette.
+    /// Error offsets are relative to the pixel data.
+    pub fn to_rgba(&self) -> Result<RgbaImage, ParseError> {
+        self.decode().within("zar")
+    }
+
+    fn decode(&self) -> Result<RgbaImage, ParseError> {
+        let size = |v: i32| {
+            u32::try_from(v).map_err(|_| ParseError::InvalidValue {
+                offset: 0,
+                expected: "non-negative image size".to_owned(),
+                found: v.to_string(),
+            })
         };
-        let unknown = <Vec<u8>>::parse(data)?;
-
-        Ok(Self {
-            magic,
-            h,
-            w,
-            data: opt,
-            unknown,
+        let (width, height) = (size(self.w)?, size(self.h)?);
+        let pixel_count = width as usize * height as usize;
+
+        let palette = self.palette.as_ref().map_or(&[][..], |p| &p.colors);
+        let color = |data: &mut Stream, alpha| {
+            let offset = data.pos();
+            let index = data.read_u8()?;
+            match palette.get(index as usize) {
+                Some(c) => {
+                    let [b, g, r, _] = c.to_le_bytes();
+                    Ok([r, g, b, alpha])
+                }
+                None => Err(ParseError::InvalidValue {
+                    offset,
+                    expected: format!("palette index below {}", palette.len()),
+                    found: index.to_string(),
+                }),
+            }
+        };
+        let [b, g, r, _] = self
+            .palette
+            .as_ref()
+            .and_then(|p| palette.get(p.shadow as usize))
+            .map_or([0; 4], |c| c.to_le_bytes());
+
+        let mut data = Stream::new(&self.runs);
+        // a run byte covers at most 63 pixels, don't trust w and h for preallocation
+        let mut pixels = Vec::with_capacity(pixel_count.min(self.runs.len() * 63) * 4);
+        while data.remain() > 0 {
+            let run = data.read_u8().within("runs")?;
+            for _ in 0..run >> 2 {
+                let pixel = match run & 3 {
+                    RUN_TRANSPARENT => Ok([0; 4]),
+                    RUN_OPAQUE => color(&mut data, 0xff),
+                    RUN_TRANSLUCENT => {
+                        color(&mut data, 0).and_then(|[r, g, b, _]| Ok([r, g, b, data.read_u8()?]))
+                    }
+                    RUN_SHADOW => data.read_u8().map(|a| [r, g, b, a]),
+                    _ => unreachable!(),
+                }
+                .within("runs")?;
+                pixels.extend_from_slice(&pixel);
+            }
+        }
+        if pixels.len() != pixel_count * 4 {
+            return Err(ParseError::InvalidValue {
+                offset: data.pos(),
+                expected: format!("{} pixels", pixel_count),
+                found: format!("{} pixels", pixels.len() / 4),
+            })
+            .within("runs");
+        }
+
+        Ok(RgbaImage {
+            width,
+            height,
+            pixels,
         })
     }
 
-    fn write<T: Write>(&self, _stream: T) -> Result<(), Error> {
-        todo!()
+    /// Replaces the image, keeping `magic`, `present` when set and the shadow index.
+    /// Colors are reduced to a 256 entry palette, pixels are written as transparent,
+    /// opaque or translucent runs.
+    pub fn set_rgba(&mut self, image: &RgbaImage) -> Result<(), Error> {
+        let size = |v: u32| {
+            i32::try_from(v).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("image size {} too large", v),
+                )
+            })
+        };
+        let (w, h) = (size(image.width)?, size(image.height)?);
+        if image.pixels.len() != w as usize * h as usize * 4 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{}x{} image has {} bytes of pixels",
+                    w,
+                    h,
+                    image.pixels.len()
+                ),
+            ));
+        }
+
+        let (palette, indices) = image.palette(256);
+        let pixels: Vec<_> = image
+            .pixels
+            .chunks_exact(4)
+            .zip(indices)
+            .map(|(p, index)| match p[3] {
+                0 => (RUN_TRANSPARENT, [0, 0], 0),
+                0xff => (RUN_OPAQUE, [index, 0], 1),
+                alpha => (RUN_TRANSLUCENT, [index, alpha], 2),
+            })
+            .collect();
+        let mut runs = Vec::new();
+        for same in pixels.chunk_by(|a, b| a.0 == b.0) {
+            for run in same.chunks(MAX_RUN) {
+                runs.push((run.len() as u8) << 2 | run[0].0);
+                for (_, data, len) in run {
+                    runs.extend_from_slice(&data[..*len]);
+                }
+            }
+        }
+
+        self.h = h;
+        self.w = w;
+        if self.present == 0 {
+            self.present = 1;
+        }
+        let shadow = self.palette.as_ref().map_or(0, |p| p.shadow);
+        self.palette = Some(ZarPalette {
+            colors: palette
+                .iter()
+                .map(|&[r, g, b]| i32::from_le_bytes([b, g, r, 0xff]))
+                .collect(),
+            shadow,
+        });
+        self.runs = runs;
+        Ok(())
     }
 }
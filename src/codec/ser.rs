@@ -0,0 +1,191 @@
+//! serde helpers: blobs are written as hex strings, magics as plain strings,
+//! floats that JSON can't hold as their bits, and embedded `.sav`/`.cam` files
+//! as their decoded sections.
+//!
+//! The compressed stream a `World` was read from is serialized too, so it imports
+//! back byte for byte.
+
+use crate::codec::primitive::FOTString;
+use crate::codec::sections::campaign_save::CampaignFile;
+use crate::codec::verify::round_trip;
+use crate::codec::Encodable;
+use crate::files::cam::Cam;
+use crate::files::sav::Sav;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub mod hex {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn encode(v: &[u8]) -> String {
+        v.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    pub fn decode(s: &str) -> Result<Vec<u8>, String> {
+        if !s.len().is_multiple_of(2) {
+            return Err(format!("odd hex length {}", s.len()));
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                s.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+                    .ok_or_else(|| format!("invalid hex at {}", i))
+            })
+            .collect()
+    }
+
+    pub fn serialize<S: Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&encode(v))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        decode(&String::deserialize(d)?).map_err(D::Error::custom)
+    }
+}
+
+/// `f32` as a JSON number when finite, otherwise as its bits, e.g. `"0x7fc00000"` for NaN.
+#[derive(Clone, Copy)]
+pub struct Lossless(pub f32);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FloatRepr {
+    Number(f32),
+    Bits(String),
+}
+
+impl Serialize for Lossless {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_finite() {
+            s.serialize_f32(self.0)
+        } else {
+            s.serialize_str(&format!("0x{:08x}", self.0.to_bits()))
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Lossless {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        match FloatRepr::deserialize(d)? {
+            FloatRepr::Number(v) => Ok(Lossless(v)),
+            FloatRepr::Bits(s) => s
+                .strip_prefix("0x")
+                .and_then(|bits| u32::from_str_radix(bits, 16).ok())
+                .map(|bits| Lossless(f32::from_bits(bits)))
+                .ok_or_else(|| D::Error::custom(format!("invalid float bits {:?}", s))),
+        }
+    }
+}
+
+pub mod float {
+    use super::Lossless;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &f32, s: S) -> Result<S::Ok, S::Error> {
+        Lossless(*v).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<f32, D::Error> {
+        Ok(Lossless::deserialize(d)?.0)
+    }
+}
+
+pub mod float_rows {
+    use super::Lossless;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &[[f32; 3]; 4], s: S) -> Result<S::Ok, S::Error> {
+        v.map(|row| row.map(Lossless)).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[[f32; 3]; 4], D::Error> {
+        let rows = <[[Lossless; 3]; 4]>::deserialize(d)?;
+        Ok(rows.map(|row| row.map(|v| v.0)))
+    }
+}
+
+pub mod cstring {
+    use serde::de::Error as _;
+    use serde::ser::Error as _;
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::CString;
+
+    pub fn serialize<S: Serializer>(v: &CString, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(v.to_str().map_err(S::Error::custom)?)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<CString, D::Error> {
+        CString::new(String::deserialize(d)?).map_err(D::Error::custom)
+    }
+}
+
+pub mod compression {
+    use flate2::Compression;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Compression, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u32(v.level())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Compression, D::Error> {
+        Ok(Compression::new(u32::deserialize(d)?))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum FileContent {
+    Sav(Box<Sav>),
+    Cam(Cam),
+    Raw(#[serde(with = "hex")] Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileRepr {
+    path: FOTString,
+    content: FileContent,
+}
+
+// files are only exported decoded when they re-encode to the exact same bytes
+impl Serialize for CampaignFile {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let content = match &*self.extension() {
+            "sav" => round_trip::<Sav>(&self.data)
+                .ok()
+                .map(|sav| FileContent::Sav(Box::new(sav))),
+            "cam" => round_trip::<Cam>(&self.data).ok().map(FileContent::Cam),
+            _ => None,
+        }
+        .unwrap_or_else(|| FileContent::Raw(self.data.clone()));
+
+        FileRepr {
+            path: self.path.clone(),
+            content,
+        }
+        .serialize(s)
+    }
+}
+
+impl<'de> Deserialize<'de> for CampaignFile {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let repr = FileRepr::deserialize(d)?;
+        let data = match repr.content {
+            FileContent::Sav(sav) => {
+                let mut data = Vec::new();
+                sav.write(&mut data).map_err(D::Error::custom)?;
+                data
+            }
+            FileContent::Cam(cam) => {
+                let mut data = Vec::new();
+                cam.write(&mut data).map_err(D::Error::custom)?;
+                data
+            }
+            FileContent::Raw(data) => data,
+        };
+        Ok(CampaignFile {
+            path: repr.path,
+            data,
+        })
+    }
+}
@@ -1,13 +1,14 @@
 #[macro_export]
 macro_rules! assert_section {
     ($data: ident, $s: ident) => {
-        let mut buf = [0; $s.len()];
-        $data.read_exact(&mut buf)?;
+        let offset = $data.pos();
+        let buf = $data.read_slice($s.len())?;
         if buf != $s.as_bytes() {
-            return Err(ParseError::InvalidSection(
-                $s,
-                String::from_utf8_lossy(&buf).into_owned(),
-            ));
+            return Err($crate::codec::error::ParseError::InvalidSection {
+                offset,
+                expected: $s,
+                found: String::from_utf8_lossy(buf).into_owned(),
+            });
         }
     };
 }
@@ -15,6 +16,7 @@ macro_rules! assert_section {
 #[macro_export]
 macro_rules! read_primitive_vec {
     ($data: ident, $t: ty, $len: expr) => {{
+        let src = $data.read_slice(($len as usize).saturating_mul(std::mem::size_of::<$t>()))?;
         let mut res: Vec<$t> = vec![0; $len as usize];
         let b = unsafe {
             core::slice::from_raw_parts_mut(
@@ -22,7 +24,7 @@ macro_rules! read_primitive_vec {
                 ($len as usize) * std::mem::size_of::<$t>(),
             )
         };
-        $data.read_exact(b)?;
+        b.copy_from_slice(src);
         res
     }};
-}
\ No newline at end of file
+}
@@ -1,9 +1,8 @@
-use crate::codec::error::ParseError;
+use crate::codec::error::{ParseError, ResultExt};
 use crate::codec::primitive::FOTString;
 use crate::codec::Encodable;
-use byteorder::{LittleEndian, ReadBytesExt};
 use std::ffi::CStr;
-use std::io::{ErrorKind, Read};
+use std::io::Read;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Stream<'a> {
@@ -18,28 +17,82 @@ impl<'a> Stream<'a> {
             cursor: data,
         }
     }
+
+    // cursor is always a suffix of buf
     pub fn pos(&self) -> usize {
-        unsafe { self.cursor.as_ptr().byte_offset_from(self.buf.as_ptr()) as _ }
+        self.buf.len() - self.cursor.len()
     }
 
     pub fn len(&self) -> usize {
         self.buf.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
     pub fn remain(&self) -> usize {
         self.cursor.len()
     }
 
+    fn ensure(&self, cnt: usize) -> Result<(), ParseError> {
+        if cnt > self.cursor.len() {
+            return Err(ParseError::UnexpectedEof {
+                offset: self.pos(),
+                wanted: cnt,
+                remain: self.cursor.len(),
+            });
+        }
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let mut res = [0; N];
+        res.copy_from_slice(self.read_slice(N)?);
+        Ok(res)
+    }
+
     pub fn skip(&mut self, cnt: usize) -> Result<(), ParseError> {
+        self.ensure(cnt)?;
         self.cursor = &self.cursor[cnt..];
         Ok(())
     }
+
+    pub fn read_u8(&mut self) -> Result<u8, ParseError> {
+        Ok(u8::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8, ParseError> {
+        Ok(i8::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, ParseError> {
+        Ok(u16::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_i16(&mut self) -> Result<i16, ParseError> {
+        Ok(i16::from_le_bytes(self.read_array()?))
+    }
+
     pub fn read_i32(&mut self) -> Result<i32, ParseError> {
-        Ok(self.cursor.read_i32::<LittleEndian>()?)
+        Ok(i32::from_le_bytes(self.read_array()?))
     }
 
     pub fn read_u32(&mut self) -> Result<u32, ParseError> {
-        Ok(self.cursor.read_u32::<LittleEndian>()?)
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub fn read_f32(&mut self) -> Result<f32, ParseError> {
+        Ok(f32::from_le_bytes(self.read_array()?))
+    }
+
+    /// Runs `f` and prefixes any error it returns with `name` in the section path.
+    pub fn section<T>(
+        &mut self,
+        name: &'static str,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        f(self).within(name)
     }
 
     pub fn read_string(&mut self) -> Result<FOTString, ParseError> {
@@ -50,14 +103,15 @@ impl<'a> Stream<'a> {
     where
         'a: 'b,
     {
-        let s = &self.cursor[..cnt];
-        self.cursor = &self.cursor[cnt..];
+        self.ensure(cnt)?;
+        let (s, rest) = self.cursor.split_at(cnt);
+        self.cursor = rest;
         Ok(s)
     }
 
     pub fn read_cstr(&mut self) -> Result<&'a CStr, ParseError> {
         let s = CStr::from_bytes_until_nul(self.cursor)
-            .map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e))?;
+            .map_err(|_| ParseError::UnterminatedString { offset: self.pos() })?;
 
         self.cursor = &self.cursor[s.to_bytes_with_nul().len()..];
         Ok(s)
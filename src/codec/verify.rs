@@ -0,0 +1,92 @@
+use crate::codec::error::{ParseError, PathSegment, SectionPath, VerifyError};
+use crate::codec::sections::campaign_save::CampaignSave;
+use crate::codec::sections::world;
+use crate::codec::stream::Stream;
+use crate::codec::Encodable;
+use crate::files::cam::Cam;
+use crate::files::sav::Sav;
+
+/// Parses `data` as `T`, writes it back and checks that the result is byte-identical.
+pub fn round_trip<'a, T: Encodable<'a>>(data: &'a [u8]) -> Result<T, VerifyError> {
+    let value = T::parse(&mut Stream::new(data))?;
+    let mut written = Vec::with_capacity(data.len());
+    value.write(&mut written)?;
+
+    if let Some(offset) = first_difference(data, &written) {
+        return Err(
+            world_mismatch::<T>(data, &written, offset).unwrap_or_else(|| VerifyError::Mismatch {
+                offset,
+                path: section_at::<T>(data, offset),
+                expected: data.get(offset).copied(),
+                found: written.get(offset).copied(),
+            }),
+        );
+    }
+    Ok(value)
+}
+
+// a change inside a world shows up somewhere in its zlib stream, so both payloads are
+// inflated and compared instead, giving an offset into the payload and the value's path
+fn world_mismatch<'a, T: Encodable<'a>>(
+    data: &'a [u8],
+    written: &[u8],
+    offset: usize,
+) -> Option<VerifyError> {
+    // input cut inside the zlib stream fails to inflate, reported at the stream's start;
+    // the header ends with two lengths, so a cut 8 bytes further is inside the stream too
+    let (mut path, start) = [offset, offset + 8].into_iter().find_map(|cut| {
+        let e = T::parse(&mut Stream::new(&data[..cut.min(data.len())])).err()?;
+        let path = e.path();
+        match (path.0.last(), e.root()) {
+            (Some(PathSegment::Name("world")), ParseError::InvalidValue { offset, .. }) => {
+                Some((path, *offset))
+            }
+            _ => None,
+        }
+    })?;
+    let expected = world::payload_at(data, start)?;
+    let found = world::payload_at(written, start)?;
+    let offset = first_difference(&expected, &found)?;
+    path.0
+        .extend(world::payload_section_at(&expected, offset).0);
+    Some(VerifyError::Mismatch {
+        offset,
+        path,
+        expected: expected.get(offset).copied(),
+        found: found.get(offset).copied(),
+    })
+}
+
+/// Runs [`round_trip`] over every `.sav` and `.cam` embedded in `save`.
+pub fn round_trip_files(save: &CampaignSave) -> Result<(), VerifyError> {
+    for (i, file) in save.files.iter().enumerate() {
+        let res = match &*file.extension() {
+            "sav" => round_trip::<Sav>(&file.data).map(drop),
+            "cam" => round_trip::<Cam>(&file.data).map(drop),
+            _ => continue,
+        };
+        res.map_err(|e| {
+            e.within(PathSegment::Index(i))
+                .within(PathSegment::Name("files"))
+                .within(PathSegment::Name("campaign_save"))
+        })?;
+    }
+    Ok(())
+}
+
+/// Offset of the first byte that differs, or where the shorter input ends.
+pub fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .or_else(|| (a.len() != b.len()).then_some(a.len().min(b.len())))
+}
+
+/// Section path of the value that reads the byte at `offset`, found by parsing
+/// the input cut at that offset. Empty if the cut input still parses.
+pub fn section_at<'a, T: Encodable<'a>>(data: &'a [u8], offset: usize) -> SectionPath {
+    match T::parse(&mut Stream::new(&data[..offset.min(data.len())])) {
+        Err(e) => e.path(),
+        Ok(_) => SectionPath::default(),
+    }
+}
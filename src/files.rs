@@ -1,2 +1,3 @@
 pub mod cam;
-pub mod sav;
\ No newline at end of file
+pub mod sav;
+pub mod save;
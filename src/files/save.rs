@@ -0,0 +1,10 @@
+use crate::codec::sections::campaign_save::CampaignSave;
+use crate::codec::sections::saveh::Saveh;
+use crate::codec::Encodable;
+
+#[derive(Debug, Encodable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Save {
+    pub saveh: Saveh,
+    pub campaign_save: CampaignSave,
+}
@@ -0,0 +1,159 @@
+//! Decoded images, with PNG input and output behind the `png` feature.
+
+use derive_debug::Dbg;
+use std::collections::BTreeMap;
+
+#[derive(Dbg, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    /// Rows top to bottom, four bytes per pixel, alpha not premultiplied.
+    #[dbg(placeholder = "...")]
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Reduces the colors to at most `max` palette entries, returns the palette and
+    /// the palette index of every pixel. Colors are kept exact when there are few enough,
+    /// otherwise the palette is built by median cut. Fully transparent pixels get index 0
+    /// and don't take up palette entries.
+    pub(crate) fn palette(&self, max: usize) -> (Vec<[u8; 3]>, Vec<u8>) {
+        let mut counts = BTreeMap::new();
+        for p in self.pixels.chunks_exact(4).filter(|p| p[3] != 0) {
+            *counts.entry([p[0], p[1], p[2]]).or_insert(0usize) += 1;
+        }
+
+        let palette: Vec<[u8; 3]> = if counts.len() <= max {
+            counts.keys().copied().collect()
+        } else {
+            median_cut(counts.into_iter().collect(), max)
+        };
+
+        let mut cache = BTreeMap::new();
+        let indices = self
+            .pixels
+            .chunks_exact(4)
+            .map(|p| {
+                if p[3] == 0 {
+                    return 0;
+                }
+                let color = [p[0], p[1], p[2]];
+                *cache
+                    .entry(color)
+                    .or_insert_with(|| nearest(&palette, color))
+            })
+            .collect();
+        (palette, indices)
+    }
+}
+
+fn nearest(palette: &[[u8; 3]], color: [u8; 3]) -> u8 {
+    let distance = |c: &[u8; 3]| {
+        (0..3)
+            .map(|i| (c[i] as i32 - color[i] as i32).pow(2))
+            .sum::<i32>()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, c)| distance(c))
+        .map_or(0, |(i, _)| i as u8)
+}
+
+// splits the box with the widest channel at its weighted median until there are `max` boxes
+fn median_cut(colors: Vec<([u8; 3], usize)>, max: usize) -> Vec<[u8; 3]> {
+    let range = |b: &[([u8; 3], usize)], ch: usize| {
+        let (min, max) = b.iter().fold((u8::MAX, 0), |(lo, hi), (c, _)| {
+            (lo.min(c[ch]), hi.max(c[ch]))
+        });
+        max.saturating_sub(min)
+    };
+    let widest = |b: &[([u8; 3], usize)]| {
+        (0..3)
+            .map(|ch| (range(b, ch), ch))
+            .max()
+            .unwrap_or_default()
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max {
+        let Some((i, ch)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| (widest(b), i))
+            .max()
+            .map(|((_, ch), i)| (i, ch))
+        else {
+            break;
+        };
+        let mut b = boxes.swap_remove(i);
+        b.sort_by_key(|(c, _)| c[ch]);
+        let total: usize = b.iter().map(|(_, n)| n).sum();
+        let mut seen = 0;
+        let split = b
+            .iter()
+            .position(|(_, n)| {
+                seen += n;
+                seen * 2 >= total
+            })
+            .map_or(1, |p| p + 1)
+            .min(b.len() - 1);
+        let rest = b.split_off(split);
+        boxes.push(b);
+        boxes.push(rest);
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let total: usize = b.iter().map(|(_, n)| n).sum();
+            std::array::from_fn(|ch| {
+                let sum: usize = b.iter().map(|(c, n)| c[ch] as usize * n).sum();
+                ((sum + total / 2) / total) as u8
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "png")]
+impl RgbaImage {
+    /// Reads any PNG, converting indexed, grayscale and 16 bit images to 8 bit RGBA.
+    pub fn read_png<R: std::io::Read>(stream: R) -> Result<Self, png::DecodingError> {
+        let mut decoder = png::Decoder::new(stream);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data)?;
+        data.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => data,
+            png::ColorType::Rgb => data
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 0xff])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => data
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            png::ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g, 0xff]).collect(),
+            // expanded by normalize_to_color8
+            png::ColorType::Indexed => unreachable!(),
+        };
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    pub fn write_png<W: std::io::Write>(&self, stream: W) -> Result<(), png::EncodingError> {
+        let mut encoder = png::Encoder::new(stream, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()
+    }
+}
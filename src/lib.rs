@@ -1,19 +1,1137 @@
 #![allow(clippy::size_of_in_element_count)]
-#![feature(pointer_byte_offsets)]
 #![feature(array_try_from_fn)]
 
+// lets fot_codec_derive refer to ::fot_codec from inside this crate too
+extern crate self as fot_codec;
+
 pub mod codec;
 pub mod files;
+pub mod image;
+pub mod model;
 
 #[cfg(test)]
 mod tests {
+    use crate::codec::census::Census;
+    use crate::codec::error::{EditError, LinkError, ParseError, VerifyError};
+    use crate::codec::math::{Frame, Rect};
+    use crate::codec::primitive::FOTString;
+    use crate::codec::sections::campaign::Campaign;
     use crate::codec::sections::campaign_save::CampaignSave;
-    use crate::codec::sections::saveh::Saveh;
+    use crate::codec::sections::entity_file::EntityFile;
+    use crate::codec::sections::esh::{Esh, EshEntry, EshValue};
+    use crate::codec::sections::saveh::{SaveTime, Saveh};
+    use crate::codec::sections::sgd::SDG;
+    use crate::codec::sections::ssg::{SSGEntry, SSG};
+    use crate::codec::sections::world::{SecondLength, World};
+    use crate::codec::sections::world_zone::WorldZone;
+    use crate::codec::sections::zar::{Zar, ZarPalette};
     use crate::codec::stream::Stream;
+    use crate::codec::verify::{first_difference, round_trip, section_at};
     use crate::codec::Encodable;
+    use crate::files;
+    use crate::image::RgbaImage;
+    use crate::model::character::{Skill, Special, Trait, HUMAN_SPECIAL_MAX};
+    use crate::model::entity::{Entity, EntityKind, Item, Link, Property};
+    use flate2::Compression;
+    use std::ffi::CString;
     use std::fs;
     use std::path::Path;
-    use crate::files;
+
+    fn sample_ssg() -> SSG {
+        let entry = |name: &str, value| EshEntry {
+            name: FOTString::Ascii(name.to_owned()),
+            value,
+        };
+        SSG {
+            unknown: [0; 0x16],
+            entity_file: EntityFile {
+                magic: CString::new("V1").unwrap(),
+                data: vec![FOTString::Ascii("entities\\human.ent".to_owned())],
+            },
+            unknown1: 0,
+            values: vec![
+                SSGEntry {
+                    id: 0,
+                    flag: 0,
+                    data: Some(Esh {
+                        magic: CString::new("V1").unwrap(),
+                        values: vec![
+                            entry("Type", EshValue::Type(FOTString::Ascii("Actor".to_owned()))),
+                            entry("Hit Points", EshValue::I32(30)),
+                            entry(
+                                "Owner",
+                                EshValue::Link {
+                                    flags: 0,
+                                    entity: 2,
+                                },
+                            ),
+                            entry("Data", EshValue::Bin(vec![1, 2, 3])),
+                            entry("Frame", EshValue::Frame(Frame::from([1.0; 12]))),
+                        ],
+                    }),
+                },
+                SSGEntry {
+                    id: 1,
+                    flag: -1,
+                    data: None,
+                },
+            ],
+        }
+    }
+
+    fn sample_saveh() -> Saveh {
+        let zar = |palette: Option<ZarPalette>| Zar {
+            magic: CString::new("V1").unwrap(),
+            h: 2,
+            w: 2,
+            present: u8::from(palette.is_some()),
+            palette,
+            runs: vec![7, 8],
+        };
+        Saveh {
+            magic: CString::new("V1").unwrap(),
+            version: 1,
+            strings: std::array::from_fn(|i| FOTString::Ascii(format!("string {}", i))),
+            tmp: std::array::from_fn(|i| {
+                zar((i % 2 == 0).then(|| ZarPalette {
+                    colors: vec![i as i32; 4],
+                    shadow: 1,
+                }))
+            }),
+            ints: [1, 2, 3, 4, 5, 6],
+        }
+    }
+
+    fn sample_world() -> World {
+        World {
+            magic: CString::new("V1").unwrap(),
+            path: FOTString::Ascii("maps\\test.bos".to_owned()),
+            sdg: SDG {
+                magic: CString::new("V1").unwrap(),
+                unknown: vec![0; 0x48],
+                names: vec![FOTString::Ascii("Joe".to_owned())],
+                replicas: vec![vec![FOTString::Ascii("Hi".to_owned())]],
+            },
+            ssg: sample_ssg(),
+            tail: vec![9; 64],
+            second_length: SecondLength::Compressed,
+            compression: Compression::fast(),
+            original: None,
+        }
+    }
+
+    #[derive(Debug, PartialEq, Encodable)]
+    #[encodable(section = "test")]
+    struct Attributes {
+        magic: CString,
+        #[encodable(prefix = "u16")]
+        short: Vec<u16>,
+        #[encodable(count = 3)]
+        fixed: Vec<u8>,
+        bytes: Vec<u8>,
+        flag: i16,
+        #[encodable(when = "*flag != -1")]
+        value: Option<i32>,
+        #[encodable(rest)]
+        rest: Vec<u8>,
+    }
+
+    #[test]
+    fn derive_attributes() {
+        let mut value = Attributes {
+            magic: CString::new("V1").unwrap(),
+            short: vec![1, 2],
+            fixed: vec![7, 8, 9],
+            bytes: vec![4, 5],
+            flag: 0,
+            value: Some(6),
+            rest: vec![0xAA, 0xBB],
+        };
+        let mut buf = Vec::new();
+        value.write(&mut buf).unwrap();
+        let expected = [
+            &b"<test>\0V1\0"[..],
+            &[2, 0, 1, 0, 2, 0],
+            &[7, 8, 9],
+            &[2, 0, 0, 0, 4, 5],
+            &[0, 0, 6, 0, 0, 0],
+            &[0xAA, 0xBB],
+        ]
+        .concat();
+        assert_eq!(buf, expected);
+        assert_eq!(round_trip::<Attributes>(&buf).unwrap(), value);
+
+        let err = Attributes::parse(&mut Stream::new(&buf[..14])).unwrap_err();
+        assert_eq!(err.path().to_string(), "test/short[1]");
+        let err = Attributes::parse(&mut Stream::new(&buf[..20])).unwrap_err();
+        assert_eq!(err.path().to_string(), "test/bytes");
+
+        value.flag = -1;
+        assert!(value.write(Vec::new()).is_err());
+        value.value = None;
+        let mut buf = Vec::new();
+        value.write(&mut buf).unwrap();
+        assert_eq!(buf.len(), expected.len() - 4);
+        assert_eq!(round_trip::<Attributes>(&buf).unwrap(), value);
+
+        value.fixed.pop();
+        assert!(value.write(Vec::new()).is_err());
+        value.fixed.push(0);
+        value.short = vec![0; 0x10000];
+        assert!(value.write(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let mut buf = Vec::new();
+        sample_ssg().write(&mut buf).unwrap();
+        SSG::parse(&mut Stream::new(&buf)).unwrap();
+        for len in 0..buf.len() {
+            assert!(SSG::parse(&mut Stream::new(&buf[..len])).is_err());
+        }
+    }
+
+    #[test]
+    fn error_reports_section_path() {
+        let mut buf = Vec::new();
+        sample_ssg().write(&mut buf).unwrap();
+        // cut inside the 48 byte frame of the first entry, the trailing placeholder entry is 6 bytes
+        let err = SSG::parse(&mut Stream::new(&buf[..buf.len() - 8])).unwrap_err();
+        assert!(matches!(err.root(), ParseError::UnexpectedEof { .. }));
+        // the payload is read in one go, so the error points at its start
+        assert_eq!(err.offset(), Some(buf.len() - 6 - 48));
+        assert!(err
+            .path()
+            .to_string()
+            .starts_with("ssg/values[0]/esh/values[4]"));
+    }
+
+    #[test]
+    fn saveh_round_trip() {
+        let mut saveh = sample_saveh();
+        // any non-zero marker means the palette follows
+        saveh.tmp[0].present = 2;
+        let mut buf = Vec::new();
+        saveh.write(&mut buf).unwrap();
+        let parsed = Saveh::parse(&mut Stream::new(&buf)).unwrap();
+        let mut again = Vec::new();
+        parsed.write(&mut again).unwrap();
+        assert_eq!(buf, again);
+        assert_eq!(parsed.tmp[0].present, 2);
+        assert!(parsed.tmp[0].palette.is_some());
+    }
+
+    #[test]
+    fn world_zone_is_written_verbatim() {
+        let buf = b"<world_zone>\0V1\0\x01\x02\x03".to_vec();
+        let zone = round_trip::<WorldZone>(&buf).unwrap();
+        assert_eq!(zone.magic.as_bytes(), b"V1");
+        assert_eq!(zone.data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn campaign_world_file_edit_is_written() {
+        let mut buf = b"<campaign>\0V1\0".to_vec();
+        buf.extend_from_slice(&[0xAA; 0x22BA - 3]);
+        FOTString::Ascii("maps\\old.bos".to_owned())
+            .write(&mut buf)
+            .unwrap();
+        buf.extend_from_slice(&[1, 2, 3]);
+
+        let mut campaign = round_trip::<Campaign>(&buf).unwrap();
+        assert_eq!(campaign.magic.as_bytes(), b"V1");
+        assert_eq!(&*campaign.world_file, "maps\\old.bos");
+        campaign.world_file = FOTString::Ascii("maps\\new.bos".to_owned());
+        let mut out = Vec::new();
+        campaign.write(&mut out).unwrap();
+
+        let parsed = Campaign::parse(&mut Stream::new(&out)).unwrap();
+        assert_eq!(&*parsed.world_file, "maps\\new.bos");
+        assert_eq!(parsed.unknown, campaign.unknown);
+        assert_eq!(parsed.tail, [1, 2, 3]);
+
+        // the fixed block keeps its size whatever the magic is
+        campaign.magic = CString::new("V12").unwrap();
+        assert!(campaign.write(Vec::new()).is_err());
+        campaign.unknown.pop();
+        let mut out = Vec::new();
+        campaign.write(&mut out).unwrap();
+        assert_eq!(out.len(), buf.len());
+
+        let err = Campaign::parse(&mut Stream::new(&buf[..0x100])).unwrap_err();
+        assert_eq!(err.path().to_string(), "campaign/unknown");
+    }
+
+    #[test]
+    fn round_trip_reports_first_difference() {
+        let esh = Esh {
+            magic: CString::new("V1").unwrap(),
+            values: vec![EshEntry {
+                name: FOTString::Ascii("Visible".to_owned()),
+                value: EshValue::Bool(true),
+            }],
+        };
+        let mut buf = Vec::new();
+        esh.write(&mut buf).unwrap();
+        round_trip::<Esh>(&buf).unwrap();
+
+        // a corrupted name is read and written back as is, but still located
+        let name = buf.windows(7).position(|w| w == b"Visible").unwrap();
+        let mut corrupted = buf.clone();
+        corrupted[name + 2] = b'X';
+        assert_eq!(first_difference(&buf, &corrupted), Some(name + 2));
+        assert_eq!(first_difference(&buf, &buf[..10]), Some(10));
+        assert_eq!(first_difference(&buf, &buf), None);
+        assert_eq!(
+            section_at::<Esh>(&corrupted, name + 2).to_string(),
+            "esh/values[0]"
+        );
+        round_trip::<Esh>(&corrupted).unwrap();
+
+        // a bool stored as 2 is read as true and written back as 1
+        let offset = buf.len() - 1;
+        buf[offset] = 2;
+        match round_trip::<Esh>(&buf) {
+            Err(VerifyError::Mismatch {
+                offset: at,
+                path,
+                expected,
+                found,
+            }) => {
+                assert_eq!(at, offset);
+                assert_eq!(path.to_string(), "esh/values[0]");
+                assert_eq!((expected, found), (Some(2), Some(1)));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    #[test]
+    fn round_trip_locates_world_payload_mismatch() {
+        use crate::files::sav::Sav;
+        use flate2::write::ZlibEncoder;
+        use std::io::Write;
+
+        let mut world = sample_world();
+        world.ssg.values[0]
+            .data
+            .as_mut()
+            .unwrap()
+            .set("Visible", EshValue::Bool(true));
+        let mut payload = Vec::new();
+        world.path.write(&mut payload).unwrap();
+        world.sdg.write(&mut payload).unwrap();
+        world.ssg.write(&mut payload).unwrap();
+        payload.extend_from_slice(&world.tail);
+        // a bool stored as 2 is written back as 1
+        let name = payload.windows(7).position(|w| w == b"Visible").unwrap();
+        let at = name + 7 + 8;
+        assert_eq!(payload[at], 1);
+        payload[at] = 2;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&payload).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut buf = b"<world>\0V1\0".to_vec();
+        buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&compressed);
+
+        let check = |res: Result<(), VerifyError>| match res {
+            Err(VerifyError::Mismatch {
+                offset,
+                path,
+                expected,
+                found,
+            }) => {
+                // relative to the inflated payload
+                assert_eq!(offset, at);
+                assert_eq!(path.to_string(), "world/ssg/values[0]/esh/values[5]");
+                assert_eq!((expected, found), (Some(2), Some(1)));
+            }
+            res => panic!("unexpected result {:?}", res),
+        };
+        check(round_trip::<World>(&buf).map(drop));
+
+        // the same inside a Sav, after the header
+        let mut sav = Vec::new();
+        sample_saveh().write(&mut sav).unwrap();
+        sav.extend_from_slice(&buf);
+        check(round_trip::<Sav>(&sav).map(drop));
+    }
+
+    #[test]
+    fn world_keeps_original_compressed_stream() {
+        let mut buf = Vec::new();
+        sample_world().write(&mut buf).unwrap();
+        let mut parsed = round_trip::<World>(&buf).unwrap();
+        assert_eq!(parsed.second_length, SecondLength::Compressed);
+        assert_eq!(parsed.compression, Compression::fast());
+
+        // the kept stream wins over the level while the payload is unchanged
+        parsed.compression = Compression::best();
+        let mut again = Vec::new();
+        parsed.write(&mut again).unwrap();
+        assert_eq!(again, buf);
+
+        // same length, other content
+        parsed.tail[0] = 1;
+        let mut edited = Vec::new();
+        parsed.write(&mut edited).unwrap();
+        assert_ne!(edited, buf);
+        assert_eq!(World::parse(&mut Stream::new(&edited)).unwrap().tail[0], 1);
+
+        parsed.tail.push(1);
+        let mut edited = Vec::new();
+        parsed.write(&mut edited).unwrap();
+        let reparsed = round_trip::<World>(&edited).unwrap();
+        assert_eq!(reparsed.tail.len(), 65);
+        assert_eq!(
+            reparsed.original.unwrap().len,
+            parsed.original.unwrap().len + 1
+        );
+        assert_eq!(reparsed.second_length, SecondLength::Compressed);
+
+        // the declared length is checked before anything is inflated
+        let mut huge = buf.clone();
+        huge[11..15].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = World::parse(&mut Stream::new(&huge)).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            ParseError::InvalidValue { offset: 11, .. }
+        ));
+    }
+
+    #[test]
+    fn ssg_entry_data_must_match_flag() {
+        let mut entry = sample_ssg().values.remove(0);
+        entry.flag = -1;
+        assert!(entry.write(Vec::new()).is_err());
+        entry.data = None;
+        assert!(entry.write(Vec::new()).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_round_trip() {
+        use crate::codec::sections::campaign_save::CampaignFile;
+        use crate::files::sav::Sav;
+
+        let mut world = sample_world();
+        let esh = world.ssg.values[0].data.as_mut().unwrap();
+        esh.set("Speed", EshValue::Float(f32::NAN));
+        esh.set("Range", EshValue::Float(f32::NEG_INFINITY));
+        let mut frame = Frame::IDENTITY;
+        frame.rows[3] = [f32::from_bits(0x7fc0_0001), -0.0, 1.5];
+        esh.set("Frame", EshValue::Frame(frame));
+        // stored blocks read back as the fast level, which flate2 compresses differently
+        world.compression = Compression::none();
+        let mut sav = Vec::new();
+        Sav {
+            saveh: sample_saveh(),
+            world,
+        }
+        .write(&mut sav)
+        .unwrap();
+        let save = CampaignSave {
+            magic: CString::new("V1").unwrap(),
+            files: vec![
+                CampaignFile {
+                    path: FOTString::Ascii("save\\test.sav".to_owned()),
+                    data: sav,
+                },
+                CampaignFile {
+                    path: FOTString::Ascii("save\\notes.txt".to_owned()),
+                    data: vec![1, 2, 3],
+                },
+            ],
+        };
+        let mut buf = Vec::new();
+        save.write(&mut buf).unwrap();
+
+        let json = serde_json::to_string(&save).unwrap();
+        assert!(json.contains("Hit Points"));
+        assert!(json.contains("\"0x7fc00001\""));
+        assert!(json.contains("\"original\""));
+        let back: CampaignSave = serde_json::from_str(&json).unwrap();
+        let mut again = Vec::new();
+        back.write(&mut again).unwrap();
+        assert_eq!(buf, again);
+
+        // without the original stream the world is recompressed
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let world = &mut value["files"][0]["content"]["Sav"]["world"];
+        world.as_object_mut().unwrap().remove("original");
+        let back: CampaignSave = serde_json::from_value(value).unwrap();
+        let mut again = Vec::new();
+        back.write(&mut again).unwrap();
+        assert_ne!(buf, again);
+    }
+
+    #[test]
+    fn zar_decodes_to_rgba() {
+        // a whole <zar> section, laid out by hand rather than by Zar::write
+        let section = [
+            &b"<zar>\0V1\0"[..],
+            &[2, 0, 0, 0, 2, 0, 0, 0],
+            // palette marker, two colors, shadow index
+            &[1, 2, 0, 0, 0],
+            &[0, 0, 0xff, 0, 0, 0xff, 0, 0],
+            &[1],
+            // opaque, transparent, translucent and shadow runs of one pixel each
+            &[8, 0, 0, 0],
+            &[0b101, 0, 0b100, 0b110, 1, 0x80, 0b111, 0x40],
+        ]
+        .concat();
+        let mut zar = round_trip::<Zar>(&section).unwrap();
+        assert_eq!((zar.w, zar.h), (2, 2));
+        let palette = zar.palette.as_ref().unwrap();
+        assert_eq!(palette.colors, [0x00ff0000, 0x0000ff00]);
+        assert_eq!(palette.shadow, 1);
+        let image = zar.to_rgba().unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.pixels,
+            [255, 0, 0, 255, 0, 0, 0, 0, 0, 255, 0, 0x80, 0, 255, 0, 0x40]
+        );
+
+        #[cfg(feature = "png")]
+        {
+            let mut png_data = Vec::new();
+            image.write_png(&mut png_data).unwrap();
+            let mut reader = png::Decoder::new(&png_data[..]).read_info().unwrap();
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut pixels).unwrap();
+            assert_eq!(pixels, image.pixels);
+        }
+
+        zar.runs[1] = 2;
+        let err = zar.to_rgba().unwrap_err();
+        assert_eq!(err.path().to_string(), "zar/runs");
+        assert_eq!(err.offset(), Some(1));
+    }
+
+    #[test]
+    fn zar_encodes_from_rgba() {
+        let image = RgbaImage {
+            width: 100,
+            height: 2,
+            pixels: (0..200u8)
+                .flat_map(|i| [i, 255 - i, i / 2, [0, 0x80, 0xff][i as usize % 3]])
+                .collect(),
+        };
+        let mut zar = sample_saveh().tmp[0].clone();
+        zar.set_rgba(&image).unwrap();
+        assert_eq!((zar.w, zar.h), (100, 2));
+        assert_eq!(zar.palette.as_ref().unwrap().shadow, 1);
+        let decoded = zar.to_rgba().unwrap();
+        // few enough colors to keep them exact, transparent pixels lose their color
+        for (a, b) in image.pixels.chunks(4).zip(decoded.pixels.chunks(4)) {
+            assert_eq!(a[3], b[3]);
+            if a[3] != 0 {
+                assert_eq!(a, b);
+            }
+        }
+
+        let many = RgbaImage {
+            width: 64,
+            height: 64,
+            pixels: (0..64 * 64u32)
+                .flat_map(|i| [i as u8, (i >> 6) as u8 * 4, (i >> 4) as u8, 0xff])
+                .collect(),
+        };
+        zar.set_rgba(&many).unwrap();
+        assert!(zar.palette.as_ref().unwrap().colors.len() <= 256);
+        assert_eq!(zar.to_rgba().unwrap().pixels.len(), many.pixels.len());
+
+        #[cfg(feature = "png")]
+        {
+            let mut png_data = Vec::new();
+            image.write_png(&mut png_data).unwrap();
+            assert_eq!(RgbaImage::read_png(&png_data[..]).unwrap(), image);
+        }
+    }
+
+    #[test]
+    fn saveh_setters_keep_layout() {
+        let mut saveh = sample_saveh();
+        saveh.ints = [0; 6];
+        assert_eq!(saveh.saved_at(), None);
+        saveh.ints = [2001, 3, 14, 9, 26, 53];
+        assert_eq!(saveh.saved_at().unwrap().to_string(), "2001-03-14 09:26:53");
+        assert!(SaveTime::new(2001, 13, 1, 0, 0, 0).is_err());
+        assert_eq!(
+            SaveTime::new(2001, 2, 29, 0, 0, 0),
+            Err(EditError::OutOfRange {
+                what: "day",
+                value: 29
+            })
+        );
+        assert!(SaveTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+        assert!(SaveTime::new(1900, 2, 29, 0, 0, 0).is_err());
+        assert!(SaveTime::new(2001, 4, 31, 0, 0, 0).is_err());
+        saveh.ints = [2001, 2, 31, 0, 0, 0];
+        assert_eq!(saveh.saved_at(), None);
+        assert_eq!(saveh.version(), 1);
+        assert_eq!(saveh.unknown_string(), "string 4");
+
+        #[cfg(feature = "unstable")]
+        {
+            let time = SaveTime::new(2001, 3, 14, 9, 26, 53).unwrap();
+            saveh.set_unknown_string("x").unwrap();
+            saveh.set_saved_at(time);
+
+            saveh.set_title("Bunker Alpha").unwrap();
+            assert!(matches!(saveh.strings[0], FOTString::Ascii(_)));
+            saveh.set_player_name("Стрелок").unwrap();
+            assert_eq!(
+                saveh.set_mission("☢"),
+                Err(EditError::Unencodable("☢".to_owned()))
+            );
+
+            let mut buf = Vec::new();
+            saveh.write(&mut buf).unwrap();
+            let parsed = Saveh::parse(&mut Stream::new(&buf)).unwrap();
+            assert_eq!(parsed.title(), "Bunker Alpha");
+            assert_eq!(parsed.player_name(), "Стрелок");
+            assert_eq!(parsed.mission(), "string 2");
+            assert_eq!(parsed.saved_at(), Some(time));
+        }
+    }
+
+    #[test]
+    fn typed_entity_keeps_unknown_properties() {
+        let mut ssg = sample_ssg();
+        let esh = ssg.values[0].data.as_mut().unwrap();
+        esh.set("Visible", EshValue::Bool(true));
+        let mut original = Vec::new();
+        ssg.write(&mut original).unwrap();
+
+        let Some(Entity::Character(mut actor)) = ssg.entity(0) else {
+            panic!("entity 0 is not a character");
+        };
+        assert_eq!(actor.hit_points, Some(30));
+        assert_eq!(actor.visible, Some(true));
+        assert_eq!(actor.name, None);
+
+        // unchanged entities are written back byte for byte
+        ssg.set_entity(0, Entity::Character(actor.clone())).unwrap();
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        assert_eq!(buf, original);
+
+        actor.hit_points = Some(12);
+        actor.visible = None;
+        actor.name = Some(FOTString::Ascii("Ghoul".to_owned()));
+        let esh = actor.into_esh();
+        let names: Vec<_> = esh.values.iter().map(|e| &*e.name).collect();
+        assert_eq!(
+            names,
+            ["Type", "Hit Points", "Owner", "Data", "Frame", "Name"]
+        );
+        assert_eq!(esh.get("Hit Points"), Some(&EshValue::I32(12)));
+        assert_eq!(esh.get("Data"), Some(&EshValue::Bin(vec![1, 2, 3])));
+        assert_eq!(EntityKind::of(&esh), EntityKind::Character);
+        assert_eq!(
+            ssg.set_entity(1, Entity::from_esh(esh)),
+            Err(EditError::NoEntity(1))
+        );
+    }
+
+    #[test]
+    fn character_stats_are_validated() {
+        let mut ssg = sample_ssg();
+        let (id, mut actor) = ssg.characters().next().unwrap();
+        assert!(ssg.player().is_none());
+        actor.esh.set("Player", EshValue::Bool(true));
+
+        actor
+            .set_special(Special::Strength, 8, HUMAN_SPECIAL_MAX)
+            .unwrap();
+        assert_eq!(
+            actor.set_special(Special::Luck, 11, HUMAN_SPECIAL_MAX),
+            Err(EditError::OutOfRange {
+                what: "Luck",
+                value: 11
+            })
+        );
+        assert!(actor
+            .set_special(Special::Luck, 0, HUMAN_SPECIAL_MAX)
+            .is_err());
+        // super mutants and deathclaws go past the human maximum
+        actor.set_special(Special::Endurance, 13, 13).unwrap();
+        actor.set_skill(Skill::SmallGuns, 150).unwrap();
+        assert!(actor.set_skill(Skill::Doctor, -1).is_err());
+        actor.set_trait(Trait::Gifted, true);
+        actor.set_trait(Trait::Finesse, true);
+        actor.set_trait(Trait::Finesse, false);
+        actor.set_trait(Trait::Jinxed, true);
+        actor.set_perk("Awareness", 1).unwrap();
+        actor.set_perk("Sniper", 1).unwrap();
+        actor.set_perk("Sniper", 0).unwrap();
+        assert_eq!(
+            actor.set_perk("Awareness", -1),
+            Err(EditError::OutOfRange {
+                what: "perk rank",
+                value: -1
+            })
+        );
+        assert_eq!(
+            actor.set_perk("Hit Points", 0),
+            Err(EditError::NotAPerk("Hit Points".to_owned()))
+        );
+        assert!(actor.set_perk("strength", 2).is_err());
+        assert!(actor.set_perk("Type", 0).is_err());
+        assert!(actor.set_level(0).is_err());
+        actor.set_level(3).unwrap();
+        actor.set_hit_points(45).unwrap();
+        assert!(actor.set_experience(-1).is_err());
+        // applied to esh right away, like the statistics without a field
+        assert_eq!(actor.esh.get("Level"), Some(&EshValue::I32(3)));
+        assert_eq!(actor.esh.get("Hit Points"), Some(&EshValue::I32(45)));
+        ssg.set_entity(id, Entity::Character(actor)).unwrap();
+
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        let ssg = SSG::parse(&mut Stream::new(&buf)).unwrap();
+        let (_, player) = ssg.player().unwrap();
+        assert_eq!(ssg.squad().len(), 1);
+        assert_eq!(player.special(Special::Strength), Some(8));
+        assert_eq!(player.special(Special::Endurance), Some(13));
+        assert_eq!(player.special(Special::Luck), None);
+        assert_eq!(player.skill(Skill::SmallGuns), Some(150));
+        assert_eq!(player.traits(), [Trait::Jinxed, Trait::Gifted]);
+        assert_eq!(player.perk("Awareness"), Some(1));
+        assert_eq!(player.perk("Sniper"), None);
+        assert_eq!(player.level, Some(3));
+        assert_eq!(player.hit_points, Some(45));
+    }
+
+    #[test]
+    fn inventory_keeps_links_intact() {
+        let mut ssg = sample_ssg();
+        let rifle = Item::from_esh(Esh {
+            magic: CString::new("V1").unwrap(),
+            values: vec![EshEntry {
+                name: FOTString::Ascii("Type".to_owned()),
+                value: EshValue::Type(FOTString::Ascii("Weapon".to_owned())),
+            }],
+        });
+        let template = "items\\rifle.ent";
+        assert_eq!(
+            ssg.add_item(0, template, rifle.clone()).unwrap_err(),
+            EditError::NoTemplate(template.to_owned())
+        );
+        assert_eq!(ssg.add_template(template).unwrap(), 1);
+        assert_eq!(
+            ssg.add_item(1, template, rifle.clone()).unwrap_err(),
+            EditError::WrongKind {
+                id: 1,
+                expected: "character or container"
+            }
+        );
+        let id = ssg.add_item(0, "Items\\Rifle.ent", rifle).unwrap();
+        assert_eq!(id, 2);
+        // bound to the rifle template, not the human one at index 0
+        assert_eq!(ssg.values[2].flag, 1);
+        ssg.set_item_count(id, 1).unwrap();
+        ssg.set_item_ammo(id, 24).unwrap();
+        assert!(ssg.set_item_count(id, 0).is_err());
+        assert_eq!(
+            ssg.set_item_ammo(0, 1),
+            Err(EditError::WrongKind {
+                id: 0,
+                expected: "item"
+            })
+        );
+
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        let mut ssg = SSG::parse(&mut Stream::new(&buf)).unwrap();
+        let inventory = ssg.inventory(0);
+        assert_eq!(inventory.len(), 1);
+        assert_eq!(inventory[0].0, id);
+        assert_eq!(inventory[0].1.ammo, Some(24));
+
+        // equipped items are linked from their owner too
+        let Some(Entity::Character(mut actor)) = ssg.entity(0) else {
+            panic!("entity 0 is not a character");
+        };
+        let equipped = ssg.link_to(id).unwrap();
+        actor.esh.set("Equipped", equipped.into_value());
+        ssg.set_entity(0, Entity::Character(actor)).unwrap();
+        assert_eq!(
+            ssg.remove_item(id).unwrap_err(),
+            EditError::Linked { id, by: 0 }
+        );
+        let Some(Entity::Character(mut actor)) = ssg.entity(0) else {
+            panic!("entity 0 is not a character");
+        };
+        actor.esh.remove("Equipped");
+        ssg.set_entity(0, Entity::Character(actor)).unwrap();
+        ssg.remove_item(id).unwrap();
+        assert!(ssg.inventory(0).is_empty());
+        assert!(ssg.values[2].data.is_none());
+    }
+
+    #[test]
+    fn link_graph_reports_broken_links() {
+        let mut ssg = sample_ssg();
+        // links hold slots, not ids: entry 1 is slot 2 whatever its id
+        ssg.values[0].id = 7;
+        ssg.values[1].id = 3;
+        assert_eq!(ssg.slot(3), Some(2));
+        assert_eq!(
+            ssg.resolve(Link {
+                entity: 2,
+                flags: 0
+            })
+            .unwrap()
+            .id,
+            3
+        );
+        assert!(ssg
+            .resolve(Link {
+                entity: 0,
+                flags: 0
+            })
+            .is_none());
+
+        let graph = ssg.link_graph();
+        let owner: Vec<_> = graph.links_to(3).collect();
+        assert_eq!(owner.len(), 1);
+        assert_eq!((owner[0].from, owner[0].property.as_str()), (7, "Owner"));
+        assert_eq!(graph.links_from(7).count(), 1);
+        assert!(graph.links_to(7).next().is_none());
+
+        let empty = LinkError::Empty {
+            from: 7,
+            property: "Owner".to_owned(),
+            target: 2,
+        };
+        assert_eq!(ssg.check_links(), Err(vec![empty]));
+
+        let owner = ssg.link_to(7).unwrap();
+        let esh = ssg.values[0].data.as_mut().unwrap();
+        esh.set("Owner", owner.into_value());
+        // slot 0 is no link at all
+        esh.set(
+            "Ally",
+            Link {
+                entity: 0,
+                flags: 0,
+            }
+            .into_value(),
+        );
+        esh.set(
+            "Target",
+            Link {
+                entity: 3,
+                flags: 0,
+            }
+            .into_value(),
+        );
+        assert_eq!(ssg.link_graph().links_to(7).count(), 1);
+        assert_eq!(
+            ssg.check_links().unwrap_err(),
+            [LinkError::Dangling {
+                from: 7,
+                property: "Target".to_owned(),
+                target: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn compact_rewrites_links() {
+        let mut ssg = sample_ssg();
+        let esh = |links: &[(&str, u16)]| Esh {
+            magic: CString::new("V1").unwrap(),
+            values: links
+                .iter()
+                .map(|&(name, slot)| EshEntry {
+                    name: FOTString::Ascii(name.to_owned()),
+                    value: Link {
+                        entity: slot,
+                        flags: 0,
+                    }
+                    .into_value(),
+                })
+                .collect(),
+        };
+        let link = |slot| {
+            Some(
+                Link {
+                    entity: slot,
+                    flags: 0,
+                }
+                .into_value(),
+            )
+        };
+        assert_eq!(
+            ssg.insert_entity(1, esh(&[])),
+            Err(EditError::OutOfRange {
+                what: "template index",
+                value: 1
+            })
+        );
+        assert_eq!(ssg.insert_entity(0, esh(&[])).unwrap(), 2);
+        assert_eq!(ssg.insert_entity(0, esh(&[("Target", 1)])).unwrap(), 3);
+        let ally = ssg.link_to(3).unwrap();
+        ssg.values[0]
+            .data
+            .as_mut()
+            .unwrap()
+            .set("Ally", ally.into_value());
+        assert_eq!(
+            ssg.delete_entity(3).unwrap_err(),
+            EditError::Linked { id: 3, by: 0 }
+        );
+        ssg.delete_entity(2).unwrap();
+        assert_eq!(ssg.values[2].flag, -1);
+        // freed slots are not reused
+        assert_eq!(ssg.insert_entity(0, esh(&[])).unwrap(), 4);
+        assert_eq!(ssg.values.len(), 5);
+        ssg.delete_entity(4).unwrap();
+
+        // slot 3 and the trailing slot 5 go, entity 3 moves from slot 4 to 3
+        let moved = ssg.compact();
+        assert_eq!(moved, [(4, 3)].into_iter().collect());
+        let ids: Vec<_> = ssg.values.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [0, 1, 3]);
+        let actor = ssg.values[0].data.as_ref().unwrap();
+        assert_eq!(actor.get("Ally").cloned(), link(3));
+        assert_eq!(actor.get("Owner").cloned(), link(2));
+        let moved = ssg.values[2].data.as_ref().unwrap();
+        assert_eq!(moved.get("Target").cloned(), link(1));
+        assert_eq!(
+            ssg.check_links(),
+            Err(vec![LinkError::Empty {
+                from: 0,
+                property: "Owner".to_owned(),
+                target: 2,
+            }])
+        );
+
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        let parsed = SSG::parse(&mut Stream::new(&buf)).unwrap();
+        assert_eq!(parsed.values.len(), 3);
+    }
+
+    #[test]
+    fn spawn_from_template() {
+        let mut ssg = sample_ssg();
+        let id = ssg.spawn("ENTITIES\\Human.ent").unwrap();
+        assert_eq!(id, 2);
+        let spawned = &ssg.values[2];
+        assert_eq!(spawned.flag, 0);
+        let esh = spawned.data.as_ref().unwrap();
+        assert_eq!(esh.get("Hit Points"), Some(&EshValue::I32(30)));
+        assert_eq!(
+            esh.get("Owner"),
+            Some(&EshValue::Link {
+                flags: 0,
+                entity: 0
+            })
+        );
+
+        assert_eq!(
+            ssg.spawn("items\\knife.ent"),
+            Err(EditError::NoPrototype("items\\knife.ent".to_owned()))
+        );
+        let knife = Esh {
+            magic: CString::new("V1").unwrap(),
+            values: vec![EshEntry {
+                name: FOTString::Ascii("Type".to_owned()),
+                value: EshValue::Type(FOTString::Ascii("Weapon".to_owned())),
+            }],
+        };
+        let id = ssg.spawn_with("items\\knife.ent", knife).unwrap();
+        assert_eq!(ssg.values[3].flag, 1);
+        assert_eq!(ssg.template_index("Items\\Knife.ent"), Some(1));
+        assert_eq!(ssg.duplicate(id).unwrap(), 4);
+        assert_eq!(ssg.values[4].flag, 1);
+
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        let parsed = SSG::parse(&mut Stream::new(&buf)).unwrap();
+        assert_eq!(parsed.entity_file.data.len(), 2);
+        assert_eq!(parsed.values.len(), 5);
+    }
+
+    #[test]
+    fn census_counts_unknown_tags() {
+        let mut ssg = sample_ssg();
+        let mut path = Vec::new();
+        FOTString::Ascii("art\\x.spr".to_owned())
+            .write(&mut path)
+            .unwrap();
+        let esh = ssg.values[0].data.as_mut().unwrap();
+        esh.set("Mystery", EshValue::Unknown(7, path));
+        esh.set("Other", EshValue::Unknown(7, vec![5, 0, 0, 0]));
+        esh.set("Odd", EshValue::Unknown(30, vec![]));
+        esh.set("Grown", EshValue::Unknown(3, vec![0; 8]));
+
+        let mut census = Census::default();
+        census.add_ssg(&ssg);
+        assert_eq!(census.tags.len(), 2);
+        assert_eq!(census.mismatched[&3].lengths[&8], 1);
+        let stats = &census.tags[&7];
+        assert_eq!(stats.count, 2);
+        assert_eq!(stats.strings, 1);
+        assert_eq!(stats.names["Mystery"], 1);
+        assert!(census.to_string().contains("tag 30: 1 values"));
+    }
+
+    #[test]
+    fn esh_payload_length_is_checked() {
+        // an I32 with an 8 byte payload, as if the type had grown
+        let mut buf = vec![3, 0, 0, 0, 8, 0, 0, 0];
+        buf.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        let value = EshValue::parse(&mut Stream::new(&buf)).unwrap();
+        assert_eq!(value, EshValue::Unknown(3, vec![1, 2, 3, 4, 5, 6, 7, 8]));
+        let mut again = Vec::new();
+        value.write(&mut again).unwrap();
+        assert_eq!(again, buf);
+
+        // a string claiming more bytes than its payload can't read into the next value
+        let mut buf = vec![4, 0, 0, 0, 4, 0, 0, 0, 16, 0, 0, 0];
+        buf.extend_from_slice(&[3, 0, 0, 0, 4, 0, 0, 0, 9, 0, 0, 0]);
+        let mut stream = Stream::new(&buf);
+        let value = EshValue::parse(&mut stream).unwrap();
+        assert_eq!(value, EshValue::Unknown(4, vec![16, 0, 0, 0]));
+        assert_eq!(EshValue::parse(&mut stream).unwrap(), EshValue::I32(9));
+
+        // lengths are taken from the written payload, two bytes per wide character
+        let value = EshValue::String(FOTString::Win1251("Привет".to_owned()));
+        let mut buf = Vec::new();
+        value.write(&mut buf).unwrap();
+        assert_eq!(buf[4..8], [16, 0, 0, 0]);
+        assert_eq!(
+            FOTString::Win1251("Привет".to_owned()).serialized_length(),
+            16
+        );
+        let wide = FOTString::Utf16("日本".to_owned());
+        let mut written = Vec::new();
+        wide.write(&mut written).unwrap();
+        assert_eq!(wide.serialized_length(), written.len());
+        assert_eq!(EshValue::parse(&mut Stream::new(&buf)).unwrap(), value);
+    }
+
+    #[test]
+    fn math_types_encode_like_arrays() {
+        let raw: [f32; 12] = std::array::from_fn(|i| i as f32);
+        let mut frame = Frame::from(raw);
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        raw.write(&mut a).unwrap();
+        frame.write(&mut b).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(frame.position(), [9.0, 10.0, 11.0]);
+        assert_eq!(<[f32; 12]>::from(frame), raw);
+
+        frame.translate([1.0, 0.0, -1.0]);
+        assert_eq!(frame.position(), [10.0, 10.0, 10.0]);
+        frame.set_rotation(Frame::IDENTITY.rotation());
+        assert_eq!(frame.rotation()[1], [0.0, 1.0, 0.0]);
+
+        let rect = Rect {
+            left: 1,
+            top: 2,
+            right: 11,
+            bottom: 7,
+        };
+        let (mut a, mut b) = (Vec::new(), Vec::new());
+        [1u32, 2, 11, 7].write(&mut a).unwrap();
+        rect.write(&mut b).unwrap();
+        assert_eq!(a, b);
+        assert_eq!((rect.width(), rect.height()), (10, 5));
+        assert!(rect.contains(1, 2) && !rect.contains(11, 2));
+
+        // teleporting an entity is a frame edit
+        let mut ssg = sample_ssg();
+        let Some(Entity::Character(mut actor)) = ssg.entity(0) else {
+            panic!("entity 0 is not a character");
+        };
+        actor.frame.as_mut().unwrap().set_position([5.0, 0.0, 5.0]);
+        ssg.set_entity(0, Entity::Character(actor)).unwrap();
+        let mut buf = Vec::new();
+        ssg.write(&mut buf).unwrap();
+        let ssg = SSG::parse(&mut Stream::new(&buf)).unwrap();
+        let Some(Entity::Character(actor)) = ssg.entity(0) else {
+            panic!("entity 0 is not a character");
+        };
+        assert_eq!(actor.frame.unwrap().position(), [5.0, 0.0, 5.0]);
+    }
+
+    #[test]
+    fn dialogue_lines_can_be_patched() {
+        let mut world = sample_world();
+        let sdg = &mut world.sdg;
+        let joe = sdg.speaker_index("Joe").unwrap();
+        assert_eq!(sdg.lines(joe).unwrap().len(), 1);
+        sdg.set_line(joe, 0, "Привет").unwrap();
+        assert_eq!(sdg.add_line(joe, "Bye").unwrap(), 1);
+        assert_eq!(
+            sdg.set_line(joe, 5, "x"),
+            Err(EditError::NoLine {
+                speaker: joe,
+                line: 5
+            })
+        );
+        assert_eq!(sdg.speaker_index("Ann"), None);
+        assert_eq!(sdg.add_line(1, "Hi"), Err(EditError::NoSpeaker(1)));
+        assert_eq!(sdg.add_speaker("Ann").unwrap(), 1);
+        sdg.add_line(1, "Hi").unwrap();
+
+        // a repeated speaker keeps its own lines, reachable by index
+        assert_eq!(sdg.add_speaker("Joe").unwrap(), 2);
+        sdg.add_line(2, "Again").unwrap();
+        assert_eq!(sdg.speaker_indices("Joe").collect::<Vec<_>>(), [0, 2]);
+        sdg.set_line(2, 0, "Once more").unwrap();
+        assert_eq!(&*sdg.lines(0).unwrap()[0], "Привет");
+
+        let mut buf = Vec::new();
+        world.write(&mut buf).unwrap();
+        let world = World::parse(&mut Stream::new(&buf)).unwrap();
+        let speakers: Vec<_> = world
+            .sdg
+            .speakers()
+            .map(|(name, lines)| (name, lines.iter().map(|l| &**l).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(
+            speakers,
+            [
+                ("Joe", vec!["Привет", "Bye"]),
+                ("Ann", vec!["Hi"]),
+                ("Joe", vec!["Once more"])
+            ]
+        );
+
+        let mut sdg = world.sdg;
+        sdg.replicas.pop();
+        assert_eq!(
+            sdg.add_speaker("Ann"),
+            Err(EditError::Unpaired {
+                names: 3,
+                replicas: 2
+            })
+        );
+        assert_eq!(sdg.names.len(), 3);
+    }
+
+    #[test]
+    fn corrupted_input_does_not_panic() {
+        let mut buf = Vec::new();
+        sample_ssg().write(&mut buf).unwrap();
+        for i in 0..buf.len() {
+            for b in [0x00, 0x7F, 0xFF] {
+                let mut corrupted = buf.clone();
+                corrupted[i] = b;
+                let _ = SSG::parse(&mut Stream::new(&corrupted));
+            }
+        }
+    }
 
     #[test]
     fn simple() {
@@ -40,4 +1158,4 @@ mod tests {
             }
         }
     }
-}
\ No newline at end of file
+}
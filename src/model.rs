@@ -0,0 +1,8 @@
+//! Typed views over the decoded sections.
+
+pub mod character;
+pub mod dialogue;
+pub mod entity;
+pub mod inventory;
+pub mod links;
+pub mod template;
@@ -0,0 +1,228 @@
+//! Character statistics: SPECIAL, skills, traits and perks, checked against the limits
+//! the game applies to every race.
+//!
+//! Every statistic is a property named as in the character screen, e.g. `Strength` or
+//! `Small Guns` holding an `I32`. Traits are `Bool` properties, perks `I32` ranks.
+//!
+//! SPECIAL maximums differ between races (super mutants and deathclaws go past 10), so
+//! the caller passes the one to check against, [`HUMAN_SPECIAL_MAX`] for humans. Traits
+//! aren't counted, as some races come with traits of their own. Setters change `esh`
+//! right away, together with the typed field when there is one.
+
+use crate::codec::error::EditError;
+use crate::codec::sections::esh::EshValue;
+use crate::codec::sections::ssg::SSG;
+use crate::model::entity::{Character, Entity, Property};
+use std::ops::RangeInclusive;
+
+const PLAYER: &str = "Player";
+const SQUAD: &str = "Squad Member";
+
+/// Highest SPECIAL value of a human character.
+pub const HUMAN_SPECIAL_MAX: i32 = 10;
+
+fn check(what: &'static str, value: i32, range: RangeInclusive<i32>) -> Result<i32, EditError> {
+    if range.contains(&value) {
+        Ok(value)
+    } else {
+        Err(EditError::OutOfRange {
+            what,
+            value: value as i64,
+        })
+    }
+}
+
+// generates an enum of named properties with `ALL` and `property()`
+macro_rules! properties {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $prop:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($variant,)*
+        }
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$variant,)*];
+
+            pub fn property(self) -> &'static str {
+                match self {
+                    $($name::$variant => $prop,)*
+                }
+            }
+        }
+    };
+}
+
+properties! {
+    Special {
+        Strength = "Strength",
+        Perception = "Perception",
+        Endurance = "Endurance",
+        Charisma = "Charisma",
+        Intelligence = "Intelligence",
+        Agility = "Agility",
+        Luck = "Luck",
+    }
+}
+
+properties! {
+    Skill {
+        SmallGuns = "Small Guns",
+        BigGuns = "Big Guns",
+        EnergyWeapons = "Energy Weapons",
+        Unarmed = "Unarmed",
+        MeleeWeapons = "Melee Weapons",
+        Throwing = "Throwing",
+        FirstAid = "First Aid",
+        Doctor = "Doctor",
+        Sneak = "Sneak",
+        Lockpick = "Lockpick",
+        Steal = "Steal",
+        Traps = "Traps",
+        Science = "Science",
+        Repair = "Repair",
+        Pilot = "Pilot",
+        Barter = "Barter",
+        Gambling = "Gambling",
+        Outdoorsman = "Outdoorsman",
+    }
+}
+
+properties! {
+    Trait {
+        FastMetabolism = "Fast Metabolism",
+        Bruiser = "Bruiser",
+        SmallFrame = "Small Frame",
+        OneHander = "One Hander",
+        Finesse = "Finesse",
+        Kamikaze = "Kamikaze",
+        HeavyHanded = "Heavy Handed",
+        FastShot = "Fast Shot",
+        BloodyMess = "Bloody Mess",
+        Jinxed = "Jinxed",
+        GoodNatured = "Good Natured",
+        ChemReliant = "Chem Reliant",
+        ChemResistant = "Chem Resistant",
+        NightPerson = "Night Person",
+        Skilled = "Skilled",
+        Gifted = "Gifted",
+    }
+}
+
+impl Character {
+    fn get<T: Property>(&self, name: &str) -> Option<T> {
+        self.esh.get(name).and_then(T::from_value)
+    }
+
+    pub fn is_player(&self) -> bool {
+        self.get(PLAYER).unwrap_or(false)
+    }
+
+    /// The player character counts as a squad member too.
+    pub fn is_squad_member(&self) -> bool {
+        self.is_player() || self.get(SQUAD).unwrap_or(false)
+    }
+
+    pub fn special(&self, stat: Special) -> Option<i32> {
+        self.get(stat.property())
+    }
+
+    /// Every race starts at 1, `max` is the race's maximum, see [`HUMAN_SPECIAL_MAX`].
+    pub fn set_special(&mut self, stat: Special, value: i32, max: i32) -> Result<(), EditError> {
+        let value = check(stat.property(), value, 1..=max)?;
+        self.esh.set(stat.property(), EshValue::I32(value));
+        Ok(())
+    }
+
+    pub fn skill(&self, skill: Skill) -> Option<i32> {
+        self.get(skill.property())
+    }
+
+    pub fn set_skill(&mut self, skill: Skill, value: i32) -> Result<(), EditError> {
+        let value = check(skill.property(), value, 0..=i32::MAX)?;
+        self.esh.set(skill.property(), EshValue::I32(value));
+        Ok(())
+    }
+
+    pub fn traits(&self) -> Vec<Trait> {
+        Trait::ALL
+            .iter()
+            .copied()
+            .filter(|t| self.get(t.property()).unwrap_or(false))
+            .collect()
+    }
+
+    pub fn set_trait(&mut self, t: Trait, on: bool) {
+        self.esh.set(t.property(), EshValue::Bool(on));
+    }
+
+    /// Rank of a perk, `None` when the character doesn't have it.
+    pub fn perk(&self, name: &str) -> Option<i32> {
+        self.get(name).filter(|&rank: &i32| rank > 0)
+    }
+
+    /// Rank 0 removes the perk. Names of other statistics and of typed fields are refused,
+    /// as are properties holding something else than a rank.
+    pub fn set_perk(&mut self, name: &str, rank: i32) -> Result<(), EditError> {
+        let stat = Special::ALL
+            .iter()
+            .map(|s| s.property())
+            .chain(Skill::ALL.iter().map(|s| s.property()))
+            .chain(Trait::ALL.iter().map(|t| t.property()))
+            .chain(Character::PROPERTIES.iter().copied())
+            .chain([PLAYER, SQUAD])
+            .any(|p| p.eq_ignore_ascii_case(name));
+        let other = self
+            .esh
+            .get(name)
+            .is_some_and(|v| !matches!(v, EshValue::I32(_)));
+        if stat || other {
+            return Err(EditError::NotAPerk(name.to_owned()));
+        }
+        match check("perk rank", rank, 0..=i32::MAX)? {
+            0 => {
+                self.esh.remove(name);
+            }
+            rank => self.esh.set(name, EshValue::I32(rank)),
+        }
+        Ok(())
+    }
+
+    pub fn set_hit_points(&mut self, value: i32) -> Result<(), EditError> {
+        self.hit_points = Some(check("hit points", value, 0..=i32::MAX)?);
+        self.esh.set("Hit Points", value.into_value());
+        Ok(())
+    }
+
+    pub fn set_experience(&mut self, value: i32) -> Result<(), EditError> {
+        self.experience = Some(check("experience", value, 0..=i32::MAX)?);
+        self.esh.set("Experience Points", value.into_value());
+        Ok(())
+    }
+
+    pub fn set_level(&mut self, value: i32) -> Result<(), EditError> {
+        self.level = Some(check("level", value, 1..=i32::MAX)?);
+        self.esh.set("Level", value.into_value());
+        Ok(())
+    }
+}
+
+impl SSG {
+    pub fn characters(&self) -> impl Iterator<Item = (i32, Character)> + '_ {
+        self.entities().filter_map(|(id, e)| match e {
+            Entity::Character(c) => Some((id, c)),
+            _ => None,
+        })
+    }
+
+    pub fn player(&self) -> Option<(i32, Character)> {
+        self.characters().find(|(_, c)| c.is_player())
+    }
+
+    /// Player character and the rest of the squad, in entity order.
+    pub fn squad(&self) -> Vec<(i32, Character)> {
+        self.characters()
+            .filter(|(_, c)| c.is_squad_member())
+            .collect()
+    }
+}
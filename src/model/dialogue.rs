@@ -0,0 +1,83 @@
+//! Dialogue lines stored in `SDG`: `replicas[i]` holds the lines spoken by `names[i]`.
+//!
+//! Speakers are addressed by index, as the same name can be stored more than once.
+
+use crate::codec::error::EditError;
+use crate::codec::primitive::FOTString;
+use crate::codec::sections::sgd::SDG;
+
+impl SDG {
+    /// Speakers with their lines, in stored order.
+    pub fn speakers(&self) -> impl Iterator<Item = (&str, &[FOTString])> {
+        self.names
+            .iter()
+            .zip(&self.replicas)
+            .map(|(name, lines)| (&**name, &lines[..]))
+    }
+
+    /// Index of the first speaker with the name. Names can repeat, see [`SDG::speaker_indices`].
+    pub fn speaker_index(&self, name: &str) -> Option<usize> {
+        self.speaker_indices(name).next()
+    }
+
+    /// Index of every speaker with the name, in stored order.
+    pub fn speaker_indices<'a>(&'a self, name: &'a str) -> impl Iterator<Item = usize> + 'a {
+        self.names
+            .iter()
+            .take(self.replicas.len())
+            .enumerate()
+            .filter(move |(_, n)| &***n == name)
+            .map(|(i, _)| i)
+    }
+
+    pub fn lines(&self, speaker: usize) -> Result<&[FOTString], EditError> {
+        self.names
+            .get(speaker)
+            .and(self.replicas.get(speaker))
+            .map(|lines| &lines[..])
+            .ok_or(EditError::NoSpeaker(speaker))
+    }
+
+    fn speaker_lines_mut(&mut self, speaker: usize) -> Result<&mut Vec<FOTString>, EditError> {
+        if speaker >= self.names.len() {
+            return Err(EditError::NoSpeaker(speaker));
+        }
+        self.replicas
+            .get_mut(speaker)
+            .ok_or(EditError::NoSpeaker(speaker))
+    }
+
+    /// Replaces a line, keeping its encoding where the text allows it.
+    pub fn set_line(&mut self, speaker: usize, line: usize, text: &str) -> Result<(), EditError> {
+        self.speaker_lines_mut(speaker)?
+            .get_mut(line)
+            .ok_or(EditError::NoLine { speaker, line })?
+            .set(text)
+    }
+
+    /// Appends a line, returns its index.
+    pub fn add_line(&mut self, speaker: usize, text: &str) -> Result<usize, EditError> {
+        let lines = self.speaker_lines_mut(speaker)?;
+        let mut line = FOTString::Ascii(String::new());
+        line.set(text)?;
+        lines.push(line);
+        Ok(lines.len() - 1)
+    }
+
+    /// Appends a speaker without lines, even if the name is already stored, and returns
+    /// its index. Fails when `names` and `replicas` already differ in length, as the new
+    /// speaker would not get its own lines.
+    pub fn add_speaker(&mut self, speaker: &str) -> Result<usize, EditError> {
+        if self.names.len() != self.replicas.len() {
+            return Err(EditError::Unpaired {
+                names: self.names.len(),
+                replicas: self.replicas.len(),
+            });
+        }
+        let mut name = FOTString::Ascii(String::new());
+        name.set(speaker)?;
+        self.names.push(name);
+        self.replicas.push(Vec::new());
+        Ok(self.names.len() - 1)
+    }
+}
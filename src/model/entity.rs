@@ -0,0 +1,358 @@
+//! Entities stored in `SSG`, as structs with the commonly used properties as fields.
+//!
+//! Property names follow the entity editor. Every struct keeps the `Esh` it was read from,
+//! so properties without a field, or with a value of an unexpected type, survive untouched.
+
+use crate::codec::error::EditError;
+use crate::codec::math::{Color, Frame, Rect};
+use crate::codec::primitive::FOTString;
+use crate::codec::sections::esh::{Esh, EshValue};
+use crate::codec::sections::ssg::SSG;
+
+/// Rust type of an `EshValue` variant.
+pub trait Property: Sized {
+    fn from_value(value: &EshValue) -> Option<Self>;
+    fn into_value(self) -> EshValue;
+}
+
+impl Property for bool {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Bool(self)
+    }
+}
+
+impl Property for f32 {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Float(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Float(self)
+    }
+}
+
+impl Property for i32 {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::I32(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::I32(self)
+    }
+}
+
+impl Property for FOTString {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::String(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::String(self)
+    }
+}
+
+impl Property for Frame {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Frame(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Frame(self)
+    }
+}
+
+impl Property for Rect {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Rect(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Rect(self)
+    }
+}
+
+impl Property for Color {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Color(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Color(self)
+    }
+}
+
+/// Reference to another entity, by its slot in the `SSG` entity table, see [`crate::model::links`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Link {
+    pub entity: u16,
+    pub flags: u16,
+}
+
+impl Property for Link {
+    fn from_value(value: &EshValue) -> Option<Self> {
+        match value {
+            EshValue::Link { flags, entity } => Some(Link {
+                entity: *entity,
+                flags: *flags,
+            }),
+            _ => None,
+        }
+    }
+
+    fn into_value(self) -> EshValue {
+        EshValue::Link {
+            flags: self.flags,
+            entity: self.entity,
+        }
+    }
+}
+
+impl Link {
+    /// Slot 0 of the entity table, which links that point nowhere hold.
+    pub fn is_none(&self) -> bool {
+        self.entity == 0
+    }
+}
+
+/// Every link property of an entity, with its name.
+pub fn links(esh: &Esh) -> impl Iterator<Item = (&str, Link)> {
+    esh.values
+        .iter()
+        .filter_map(|e| Some((&*e.name, Link::from_value(&e.value)?)))
+}
+
+// fields are `Option`s, None when the property is missing or holds another type
+macro_rules! entity {
+    (
+        $(#[$meta:meta])*
+        $name:ident {
+            $($(#[$field_meta:meta])* $field:ident: $ty:ty = $prop:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone)]
+        pub struct $name {
+            $($(#[$field_meta])* pub $field: Option<$ty>,)*
+            /// Properties as read, written back with the fields above applied.
+            pub esh: Esh,
+        }
+
+        impl $name {
+            /// Names of the properties behind the typed fields.
+            pub const PROPERTIES: &'static [&'static str] = &[$($prop,)*];
+
+            pub fn from_esh(esh: Esh) -> Self {
+                Self {
+                    $($field: esh.get($prop).and_then(Property::from_value),)*
+                    esh,
+                }
+            }
+
+            /// Set fields replace their property in place or are appended,
+            /// cleared ones are removed unless the property holds another type.
+            pub fn into_esh(mut self) -> Esh {
+                $(match self.$field {
+                    Some(v) => self.esh.set($prop, v.into_value()),
+                    None => {
+                        if self.esh.get($prop).and_then(<$ty>::from_value).is_some() {
+                            self.esh.remove($prop);
+                        }
+                    }
+                })*
+                self.esh
+            }
+        }
+    };
+}
+
+entity! {
+    /// `Actor`, player characters, NPCs and creatures.
+    Character {
+        name: FOTString = "Name",
+        hit_points: i32 = "Hit Points",
+        action_points: i32 = "Action Points",
+        level: i32 = "Level",
+        experience: i32 = "Experience Points",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+entity! {
+    /// Weapons, ammo, armour and other things that can be carried.
+    Item {
+        name: FOTString = "Name",
+        /// Stack size.
+        count: i32 = "Count",
+        /// Rounds loaded in a weapon.
+        ammo: i32 = "Ammo Count",
+        /// Character or container carrying the item.
+        owner: Link = "Owner",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+entity! {
+    Container {
+        name: FOTString = "Name",
+        locked: bool = "Locked",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+entity! {
+    Door {
+        name: FOTString = "Name",
+        locked: bool = "Locked",
+        open: bool = "Open",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+entity! {
+    Trigger {
+        name: FOTString = "Name",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+entity! {
+    /// Any other kind, only the common properties are typed.
+    Generic {
+        name: FOTString = "Name",
+        visible: bool = "Visible",
+        frame: Frame = "Frame",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityKind {
+    Character,
+    Item,
+    Container,
+    Door,
+    Trigger,
+    /// Another `Type`, or none at all.
+    Other(Option<String>),
+}
+
+impl EntityKind {
+    pub fn of(esh: &Esh) -> Self {
+        let Some(name) = esh.type_name() else {
+            return EntityKind::Other(None);
+        };
+        match name.to_ascii_lowercase().as_str() {
+            "actor" => EntityKind::Character,
+            "item" | "weapon" | "ammo" | "armour" | "key" => EntityKind::Item,
+            "container" => EntityKind::Container,
+            "door" => EntityKind::Door,
+            "trigger" => EntityKind::Trigger,
+            _ => EntityKind::Other(Some(name.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Entity {
+    Character(Character),
+    Item(Item),
+    Container(Container),
+    Door(Door),
+    Trigger(Trigger),
+    Other(Generic),
+}
+
+impl Entity {
+    pub fn from_esh(esh: Esh) -> Self {
+        match EntityKind::of(&esh) {
+            EntityKind::Character => Entity::Character(Character::from_esh(esh)),
+            EntityKind::Item => Entity::Item(Item::from_esh(esh)),
+            EntityKind::Container => Entity::Container(Container::from_esh(esh)),
+            EntityKind::Door => Entity::Door(Door::from_esh(esh)),
+            EntityKind::Trigger => Entity::Trigger(Trigger::from_esh(esh)),
+            EntityKind::Other(_) => Entity::Other(Generic::from_esh(esh)),
+        }
+    }
+
+    pub fn into_esh(self) -> Esh {
+        match self {
+            Entity::Character(e) => e.into_esh(),
+            Entity::Item(e) => e.into_esh(),
+            Entity::Container(e) => e.into_esh(),
+            Entity::Door(e) => e.into_esh(),
+            Entity::Trigger(e) => e.into_esh(),
+            Entity::Other(e) => e.into_esh(),
+        }
+    }
+
+    /// Properties as read, without the changes made to typed fields.
+    pub fn esh(&self) -> &Esh {
+        match self {
+            Entity::Character(e) => &e.esh,
+            Entity::Item(e) => &e.esh,
+            Entity::Container(e) => &e.esh,
+            Entity::Door(e) => &e.esh,
+            Entity::Trigger(e) => &e.esh,
+            Entity::Other(e) => &e.esh,
+        }
+    }
+
+    pub fn kind(&self) -> EntityKind {
+        EntityKind::of(self.esh())
+    }
+}
+
+impl SSG {
+    /// Entities with their ids, entries without data are skipped.
+    pub fn entities(&self) -> impl Iterator<Item = (i32, Entity)> + '_ {
+        self.values
+            .iter()
+            .filter_map(|e| Some((e.id, Entity::from_esh(e.data.clone()?))))
+    }
+
+    pub fn entity(&self, id: i32) -> Option<Entity> {
+        let entry = self.values.iter().find(|e| e.id == id)?;
+        Some(Entity::from_esh(entry.data.clone()?))
+    }
+
+    /// Writes an entity back to the entry with the given id.
+    /// The entry's flag is kept, it has to be set already for the entry to hold data.
+    pub fn set_entity(&mut self, id: i32, entity: Entity) -> Result<(), EditError> {
+        let entry = self
+            .values
+            .iter_mut()
+            .find(|e| e.id == id && e.data.is_some())
+            .ok_or(EditError::NoEntity(id))?;
+        entry.data = Some(entity.into_esh());
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Items carried by characters and stored in containers, linked through their `Owner` property.
+
+use crate::codec::error::EditError;
+use crate::codec::sections::ssg::SSG;
+use crate::model::entity::{Entity, EntityKind, Item, Link};
+
+impl SSG {
+    fn kind_of(&self, id: i32) -> Result<EntityKind, EditError> {
+        let entry = self
+            .values
+            .iter()
+            .find(|e| e.id == id)
+            .ok_or(EditError::NoEntity(id))?;
+        Ok(entry
+            .data
+            .as_ref()
+            .map_or(EntityKind::Other(None), EntityKind::of))
+    }
+
+    fn item(&self, id: i32) -> Result<Item, EditError> {
+        match self.entity(id) {
+            Some(Entity::Item(item)) => Ok(item),
+            Some(_) => Err(EditError::WrongKind {
+                id,
+                expected: "item",
+            }),
+            None => Err(EditError::NoEntity(id)),
+        }
+    }
+
+    fn check_owner(&self, owner: i32) -> Result<Link, EditError> {
+        match self.kind_of(owner)? {
+            EntityKind::Character | EntityKind::Container => {
+                self.link_to(owner).ok_or(EditError::NoEntity(owner))
+            }
+            _ => Err(EditError::WrongKind {
+                id: owner,
+                expected: "character or container",
+            }),
+        }
+    }
+
+    /// Items whose `Owner` links to the given character or container.
+    pub fn inventory(&self, owner: i32) -> Vec<(i32, Item)> {
+        let Some(slot) = self.slot(owner) else {
+            return Vec::new();
+        };
+        self.entities()
+            .filter_map(|(id, e)| match e {
+                Entity::Item(item) if item.owner.is_some_and(|l| l.entity == slot) => {
+                    Some((id, item))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Smallest id above all existing ones.
+    pub fn next_id(&self) -> Option<i32> {
+        self.values
+            .iter()
+            .map(|e| e.id)
+            .max()
+            .map_or(Some(0), |id| id.checked_add(1))
+    }
+
+    /// Adds the item as a new entity made from the template and owned by `owner`,
+    /// returns its id. The template has to be listed already, see [`SSG::add_template`].
+    pub fn add_item(
+        &mut self,
+        owner: i32,
+        template: &str,
+        mut item: Item,
+    ) -> Result<i32, EditError> {
+        let index = self
+            .template_index(template)
+            .ok_or_else(|| EditError::NoTemplate(template.to_owned()))?;
+        let link = self.check_owner(owner)?;
+        item.owner = Some(link);
+        self.insert_entity(index, item.into_esh())
+    }
+
+    /// Moves the item to another character or container.
+    pub fn move_item(&mut self, id: i32, owner: i32) -> Result<(), EditError> {
+        let link = self.check_owner(owner)?;
+        let mut item = self.item(id)?;
+        item.owner = Some(link);
+        self.set_entity(id, Entity::Item(item))
+    }
+
+    /// Removes the item, refused while another entity links to it,
+    /// e.g. an item stored inside it or a character having it equipped.
+    pub fn remove_item(&mut self, id: i32) -> Result<Item, EditError> {
+        self.item(id)?;
+        self.delete_entity(id).map(Item::from_esh)
+    }
+
+    pub fn set_item_count(&mut self, id: i32, count: i32) -> Result<(), EditError> {
+        if count < 1 {
+            return Err(EditError::OutOfRange {
+                what: "item count",
+                value: count as i64,
+            });
+        }
+        let mut item = self.item(id)?;
+        item.count = Some(count);
+        self.set_entity(id, Entity::Item(item))
+    }
+
+    pub fn set_item_ammo(&mut self, id: i32, ammo: i32) -> Result<(), EditError> {
+        if ammo < 0 {
+            return Err(EditError::OutOfRange {
+                what: "ammo count",
+                value: ammo as i64,
+            });
+        }
+        let mut item = self.item(id)?;
+        item.ammo = Some(ammo);
+        self.set_entity(id, Entity::Item(item))
+    }
+}
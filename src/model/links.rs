@@ -0,0 +1,217 @@
+//! Links between entities and the entity table edits that have to keep them intact.
+//!
+//! A link holds a slot in the game's entity table rather than an id. The table is one
+//! longer than `SSG::values`: slot 0 is reserved for "no link" and entry `i` is slot `i + 1`.
+
+use crate::codec::error::{EditError, LinkError};
+use crate::codec::sections::esh::{Esh, EshValue};
+use crate::codec::sections::ssg::{SSGEntry, SSG};
+use crate::model::entity::{links, Link};
+use std::collections::HashMap;
+
+/// A link property of one entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkRef {
+    pub from: i32,
+    pub property: String,
+    pub link: Link,
+    /// Id of the entry the link points at, `None` when its slot is past the table.
+    pub target: Option<i32>,
+}
+
+/// Every link in an `SSG`, indexed both ways by entity id. Links to slot 0 are left out.
+/// Built once, it doesn't follow later edits.
+#[derive(Debug, Clone, Default)]
+pub struct LinkGraph {
+    links: Vec<LinkRef>,
+    from: HashMap<i32, Vec<usize>>,
+    to: HashMap<i32, Vec<usize>>,
+}
+
+impl LinkGraph {
+    pub fn new(ssg: &SSG) -> Self {
+        let mut graph = Self::default();
+        for entry in &ssg.values {
+            let Some(esh) = &entry.data else { continue };
+            for (property, link) in links(esh).filter(|(_, l)| !l.is_none()) {
+                let link = LinkRef {
+                    from: entry.id,
+                    property: property.to_owned(),
+                    link,
+                    target: ssg.resolve(link).map(|e| e.id),
+                };
+                let i = graph.links.len();
+                graph.from.entry(link.from).or_default().push(i);
+                if let Some(target) = link.target {
+                    graph.to.entry(target).or_default().push(i);
+                }
+                graph.links.push(link);
+            }
+        }
+        graph
+    }
+
+    pub fn links(&self) -> &[LinkRef] {
+        &self.links
+    }
+
+    /// Links held by the entity.
+    pub fn links_from(&self, id: i32) -> impl Iterator<Item = &LinkRef> {
+        self.from
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.links[i])
+    }
+
+    /// Links pointing at the entity, "who links to entity N".
+    pub fn links_to(&self, id: i32) -> impl Iterator<Item = &LinkRef> {
+        self.to
+            .get(&id)
+            .into_iter()
+            .flatten()
+            .map(|&i| &self.links[i])
+    }
+}
+
+impl SSG {
+    pub fn link_graph(&self) -> LinkGraph {
+        LinkGraph::new(self)
+    }
+
+    /// Slot of the entity with the id, `None` if there is none.
+    pub fn slot(&self, id: i32) -> Option<u16> {
+        let i = self.values.iter().position(|e| e.id == id)?;
+        u16::try_from(i + 1).ok()
+    }
+
+    /// Link to the entity with the id, `None` if there is none.
+    pub fn link_to(&self, id: i32) -> Option<Link> {
+        Some(Link {
+            entity: self.slot(id)?,
+            flags: 0,
+        })
+    }
+
+    /// Entry a link points at, `None` for slot 0 and slots past the table.
+    pub fn resolve(&self, link: Link) -> Option<&SSGEntry> {
+        let i = usize::from(link.entity).checked_sub(1)?;
+        self.values.get(i)
+    }
+
+    /// Links pointing past the table or at entries without data, which the game
+    /// doesn't survive loading. Meant to be run before writing an edited save.
+    pub fn check_links(&self) -> Result<(), Vec<LinkError>> {
+        let errors: Vec<_> = LinkGraph::new(self)
+            .links
+            .into_iter()
+            .filter_map(|link| match self.resolve(link.link) {
+                None => Some(LinkError::Dangling {
+                    from: link.from,
+                    property: link.property,
+                    target: link.link.entity,
+                }),
+                Some(entry) if entry.data.is_none() => Some(LinkError::Empty {
+                    from: link.from,
+                    property: link.property,
+                    target: link.link.entity,
+                }),
+                Some(_) => None,
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// entries can't be removed without moving every slot after them,
+// so deleted entities become placeholders: flag -1 and no data
+const PLACEHOLDER: i16 = -1;
+
+impl SSG {
+    /// Turns the entity into a placeholder slot, keeping every other slot as is.
+    /// Refused while another entity links to it.
+    pub fn delete_entity(&mut self, id: i32) -> Result<Esh, EditError> {
+        if let Some(link) = self.link_graph().links_to(id).find(|l| l.from != id) {
+            return Err(EditError::Linked { id, by: link.from });
+        }
+        let entry = self
+            .values
+            .iter_mut()
+            .find(|e| e.id == id && e.data.is_some())
+            .ok_or(EditError::NoEntity(id))?;
+        entry.flag = PLACEHOLDER;
+        Ok(entry.data.take().unwrap())
+    }
+
+    /// Appends the entity with a fresh id, made from the template at that index
+    /// of `entity_file`. Returns the id.
+    ///
+    /// Placeholder slots are never reused: something outside this `SSG` may still
+    /// refer to the entity that was there.
+    pub fn insert_entity(&mut self, template: i16, esh: Esh) -> Result<i32, EditError> {
+        if !(0..self.entity_file.data.len()).contains(&(template as usize)) {
+            return Err(EditError::OutOfRange {
+                what: "template index",
+                value: template as i64,
+            });
+        }
+        // the entry count is written as an i16 and counts slot 0 too
+        let max = i16::MAX as usize - 1;
+        if self.values.len() >= max {
+            return Err(EditError::TooMany {
+                what: "entities",
+                max,
+            });
+        }
+        let id = self.next_id().ok_or(EditError::TooMany {
+            what: "entity ids",
+            max: i32::MAX as usize,
+        })?;
+        self.values.push(SSGEntry {
+            id,
+            flag: template,
+            data: Some(esh),
+        });
+        Ok(id)
+    }
+
+    /// Drops placeholder slots nothing in this `SSG` links to, moving the entries after
+    /// them down and rewriting every link to the slots that moved. Ids are kept.
+    /// Returns the new slot of each slot that moved.
+    pub fn compact(&mut self) -> HashMap<u16, u16> {
+        let graph = self.link_graph();
+        let mut slots = HashMap::new();
+        let mut slot = 0u16;
+        let mut old = 0u16;
+        self.values.retain(|e| {
+            old += 1;
+            let keep = e.data.is_some() || graph.links_to(e.id).next().is_some();
+            if keep {
+                slot += 1;
+                if slot != old {
+                    slots.insert(old, slot);
+                }
+            }
+            keep
+        });
+        self.rewrite_links(&slots);
+        slots
+    }
+
+    /// Points links at new slots, links to slots missing from `slots` are kept.
+    pub fn rewrite_links(&mut self, slots: &HashMap<u16, u16>) {
+        for esh in self.values.iter_mut().filter_map(|e| e.data.as_mut()) {
+            for entry in &mut esh.values {
+                if let EshValue::Link { entity, .. } = &mut entry.value {
+                    if let Some(new) = slots.get(entity) {
+                        *entity = *new;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Spawning entities from the templates listed in `SSG::entity_file`.
+//!
+//! An entry's `flag` is the index of its template in `entity_file.data`.
+
+use crate::codec::error::EditError;
+use crate::codec::primitive::FOTString;
+use crate::codec::sections::esh::{Esh, EshValue};
+use crate::codec::sections::ssg::SSG;
+
+impl SSG {
+    /// Index of a template path, compared case-insensitively like the game's file system.
+    pub fn template_index(&self, path: &str) -> Option<i16> {
+        self.entity_file
+            .data
+            .iter()
+            .position(|p| p.eq_ignore_ascii_case(path))
+            .map(|i| i as i16)
+    }
+
+    /// Index of a template path, added to the list if missing.
+    pub fn add_template(&mut self, path: &str) -> Result<i16, EditError> {
+        if let Some(i) = self.template_index(path) {
+            return Ok(i);
+        }
+        let index = i16::try_from(self.entity_file.data.len()).map_err(|_| EditError::TooMany {
+            what: "templates",
+            max: i16::MAX as usize,
+        })?;
+        let mut name = FOTString::Ascii(String::new());
+        name.set(path)?;
+        self.entity_file.data.push(name);
+        Ok(index)
+    }
+
+    /// Spawns a copy of the first entity made from the template. Links are cleared to
+    /// slot 0, a copy sharing its inventory or owner with the original would break both.
+    ///
+    /// Fails with [`EditError::NoPrototype`] when no entity in the save uses the template.
+    /// The properties an entity needs depend on its type and are not known to this crate,
+    /// so in that case the caller has to build them and use [`SSG::spawn_with`].
+    pub fn spawn(&mut self, template: &str) -> Result<i32, EditError> {
+        let prototype = self.template_index(template).and_then(|index| {
+            self.values
+                .iter()
+                .find(|e| e.flag == index)
+                .and_then(|e| e.data.clone())
+        });
+        match prototype {
+            Some(esh) => self.spawn_with(template, without_links(esh)),
+            None => Err(EditError::NoPrototype(template.to_owned())),
+        }
+    }
+
+    /// Spawns an entity with the given properties from the template, adding the template
+    /// if needed. Use this when the save has no entity of that template to copy.
+    pub fn spawn_with(&mut self, template: &str, esh: Esh) -> Result<i32, EditError> {
+        let index = self.add_template(template)?;
+        self.insert_entity(index, esh)
+    }
+
+    /// Copies an entity, links cleared as in [`SSG::spawn`].
+    pub fn duplicate(&mut self, id: i32) -> Result<i32, EditError> {
+        let entry = self
+            .values
+            .iter()
+            .find(|e| e.id == id && e.data.is_some())
+            .ok_or(EditError::NoEntity(id))?;
+        let (flag, esh) = (entry.flag, without_links(entry.data.clone().unwrap()));
+        self.insert_entity(flag, esh)
+    }
+}
+
+// the properties stay so the copy has the same layout as the original
+fn without_links(mut esh: Esh) -> Esh {
+    for entry in &mut esh.values {
+        if let EshValue::Link { entity, .. } = &mut entry.value {
+            *entity = 0;
+        }
+    }
+    esh
+}